@@ -0,0 +1,73 @@
+use std::time::{Duration, Instant};
+
+use ebi::front::{tokenize, tokenize_scalar};
+use ebi::{NullErrors, SourceFile};
+
+const SIZE: usize = 16 * 1024 * 1024;
+
+const HANDWRITTEN: &[u8] = b"\
+/// Entity number
+class Entity {
+    x: Float = 0.0
+    y: Float = 1.5e3
+    fn update(dt: Float, scale: Float) -> Float {
+        let acc = x * dt + scale // integrate
+        if acc >= 0x7fff_ffff { return \"overflow\" }
+        return acc
+    }
+}
+
+";
+
+const GENERATED: &[u8] = b"\
+/// Generated from schema definition `generated_entity_component_0123`
+class GeneratedEntityComponent_0123 {
+    fn update_velocity_component(delta_time_seconds: Float, gravity_scale_factor: Float) -> Float {
+        if generated_entity_component_accumulated_value >= generated_entity_component_limit {
+                        generated_entity_component_accumulated_value = generated_entity_component_limit
+        }
+        return generated_entity_component_accumulated_value
+    }
+}
+
+";
+
+fn synthetic_source(chunk: &[u8]) -> Vec<u8> {
+    let mut source = Vec::with_capacity(SIZE + chunk.len());
+    while source.len() < SIZE {
+        source.extend_from_slice(chunk);
+    }
+    source
+}
+
+fn bench(name: &str, source: &[u8]) {
+    let file = SourceFile::new(0);
+
+    // Alternate between the two tokenizers to even out noise from the machine.
+    let mut scalar_best = Duration::MAX;
+    let mut fast_best = Duration::MAX;
+    let mut token_count = 0;
+    for _ in 0..40 {
+        let start = Instant::now();
+        let scalar_count = tokenize_scalar(&NullErrors, file, source).count();
+        scalar_best = scalar_best.min(start.elapsed());
+
+        let start = Instant::now();
+        token_count = tokenize(&NullErrors, file, source).count();
+        fast_best = fast_best.min(start.elapsed());
+
+        assert_eq!(scalar_count, token_count);
+    }
+
+    let mb = source.len() as f64 / (1024.0 * 1024.0);
+    let scalar = mb / scalar_best.as_secs_f64();
+    let fast = mb / fast_best.as_secs_f64();
+    println!("{name}: {mb:.1} MB, {token_count} tokens");
+    println!("  scalar: {scalar:8.1} MB/s");
+    println!("  fast:   {fast:8.1} MB/s ({:.2}x)", fast / scalar);
+}
+
+fn main() {
+    bench("handwritten", &synthetic_source(HANDWRITTEN));
+    bench("generated", &synthetic_source(GENERATED));
+}
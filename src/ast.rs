@@ -1,3 +1,5 @@
+use std::{collections::HashMap, sync::Arc};
+
 use bumpalo::Bump;
 use self_cell::self_cell;
 
@@ -9,25 +11,123 @@ pub enum TokenKind {
     Ident,
     Integer,
     Float,
+    String,
+    Char,
     Assign,
     Equals,
+    NotEquals,
     Greater,
     Less,
     GreaterEquals,
     LessEquals,
     BraceOpen,
     BraceClose,
+    ParenOpen,
+    ParenClose,
+    BracketOpen,
+    BracketClose,
     Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Not,
+    BitNot,
+    LogicalAnd,
+    LogicalOr,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    AddAssign,
+    SubAssign,
+    MulAssign,
+    DivAssign,
+    ModAssign,
+    BitAndAssign,
+    BitOrAssign,
+    BitXorAssign,
+    ShiftLeftAssign,
+    ShiftRightAssign,
+    Comma,
+    Dot,
+    DotDot,
+    DotDotEq,
+    Colon,
+    ColonColon,
+    Semicolon,
+    Arrow,
+    FatArrow,
+    Question,
+    Underscore,
     Newline,
+    DocComment,
+    InnerDocComment,
     End,
     KeywordClass,
     KeywordStruct,
+    KeywordFn,
+    KeywordLet,
+    KeywordVar,
+    KeywordIf,
+    KeywordElse,
+    KeywordWhile,
+    KeywordFor,
+    KeywordIn,
+    KeywordReturn,
+    KeywordBreak,
+    KeywordContinue,
+    KeywordImport,
+    KeywordMatch,
+    KeywordPub,
+    KeywordTrue,
+    KeywordFalse,
+    KeywordNull,
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum ContextualKeyword {
+    From,
+    As,
+    Where,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenValue {
+    None,
+    Integer(u64),
+    Float(f64),
+    Char(char),
+    Contextual(ContextualKeyword),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Trivia {
+    pub leading: u32,
+    pub trailing: u32,
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    LineComment,
+    BlockComment,
+    ByteOrderMark,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TriviaPiece {
+    pub kind: TriviaKind,
+    pub span: SourceSpan,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub kind: TokenKind,
     pub span: SourceSpan,
+    pub value: TokenValue,
+    pub trivia: Trivia,
 }
 
 impl Token {
@@ -35,33 +135,872 @@ impl Token {
         Token {
             kind: TokenKind::Error,
             span: SourceSpan::unknown(),
+            value: TokenValue::None,
+            trivia: Trivia::default(),
         }
     }
     pub fn end() -> Token {
         Token {
             kind: TokenKind::End,
             span: SourceSpan::unknown(),
+            value: TokenValue::None,
+            trivia: Trivia::default(),
         }
     }
+
+    pub fn leading_trivia(&self) -> SourceSpan {
+        let begin = self.span.begin() - self.trivia.leading as usize;
+        SourceSpan::new(self.span.file(), begin, self.span.begin())
+    }
+
+    pub fn trailing_trivia(&self) -> SourceSpan {
+        let end = self.span.end() + self.trivia.trailing as usize;
+        SourceSpan::new(self.span.file(), self.span.end(), end)
+    }
 }
 
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 pub enum BinaryOp {
     Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Equals,
+    NotEquals,
+    Less,
+    Greater,
+    LessEquals,
+    GreaterEquals,
+    LogicalAnd,
+    LogicalOr,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Assign,
+    AddAssign,
+    SubAssign,
+    MulAssign,
+    DivAssign,
+    ModAssign,
+    BitAndAssign,
+    BitOrAssign,
+    BitXorAssign,
+    ShiftLeftAssign,
+    ShiftRightAssign,
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+    None,
+}
+
+impl BinaryOp {
+    pub fn from_token(kind: TokenKind) -> Option<BinaryOp> {
+        let op = match kind {
+            TokenKind::Add => BinaryOp::Add,
+            TokenKind::Sub => BinaryOp::Sub,
+            TokenKind::Mul => BinaryOp::Mul,
+            TokenKind::Div => BinaryOp::Div,
+            TokenKind::Mod => BinaryOp::Mod,
+            TokenKind::Equals => BinaryOp::Equals,
+            TokenKind::NotEquals => BinaryOp::NotEquals,
+            TokenKind::Less => BinaryOp::Less,
+            TokenKind::Greater => BinaryOp::Greater,
+            TokenKind::LessEquals => BinaryOp::LessEquals,
+            TokenKind::GreaterEquals => BinaryOp::GreaterEquals,
+            TokenKind::LogicalAnd => BinaryOp::LogicalAnd,
+            TokenKind::LogicalOr => BinaryOp::LogicalOr,
+            TokenKind::BitAnd => BinaryOp::BitAnd,
+            TokenKind::BitOr => BinaryOp::BitOr,
+            TokenKind::BitXor => BinaryOp::BitXor,
+            TokenKind::ShiftLeft => BinaryOp::ShiftLeft,
+            TokenKind::ShiftRight => BinaryOp::ShiftRight,
+            TokenKind::Assign => BinaryOp::Assign,
+            TokenKind::AddAssign => BinaryOp::AddAssign,
+            TokenKind::SubAssign => BinaryOp::SubAssign,
+            TokenKind::MulAssign => BinaryOp::MulAssign,
+            TokenKind::DivAssign => BinaryOp::DivAssign,
+            TokenKind::ModAssign => BinaryOp::ModAssign,
+            TokenKind::BitAndAssign => BinaryOp::BitAndAssign,
+            TokenKind::BitOrAssign => BinaryOp::BitOrAssign,
+            TokenKind::BitXorAssign => BinaryOp::BitXorAssign,
+            TokenKind::ShiftLeftAssign => BinaryOp::ShiftLeftAssign,
+            TokenKind::ShiftRightAssign => BinaryOp::ShiftRightAssign,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// Binding power of the operator, higher binds tighter.
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Assign
+            | BinaryOp::AddAssign
+            | BinaryOp::SubAssign
+            | BinaryOp::MulAssign
+            | BinaryOp::DivAssign
+            | BinaryOp::ModAssign
+            | BinaryOp::BitAndAssign
+            | BinaryOp::BitOrAssign
+            | BinaryOp::BitXorAssign
+            | BinaryOp::ShiftLeftAssign
+            | BinaryOp::ShiftRightAssign => 1,
+            BinaryOp::LogicalOr => 2,
+            BinaryOp::LogicalAnd => 3,
+            BinaryOp::Equals
+            | BinaryOp::NotEquals
+            | BinaryOp::Less
+            | BinaryOp::Greater
+            | BinaryOp::LessEquals
+            | BinaryOp::GreaterEquals => 4,
+            BinaryOp::BitOr => 5,
+            BinaryOp::BitXor => 6,
+            BinaryOp::BitAnd => 7,
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => 8,
+            BinaryOp::Add | BinaryOp::Sub => 9,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => 10,
+        }
+    }
+
+    pub fn associativity(self) -> Associativity {
+        match self.precedence() {
+            1 => Associativity::Right,
+            4 => Associativity::None,
+            _ => Associativity::Left,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    BitNot,
+}
+
+impl UnaryOp {
+    pub fn from_token(kind: TokenKind) -> Option<UnaryOp> {
+        match kind {
+            TokenKind::Sub => Some(UnaryOp::Neg),
+            TokenKind::Not => Some(UnaryOp::Not),
+            TokenKind::BitNot => Some(UnaryOp::BitNot),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub enum Ast<'a> {
     Error(Token),
-    Root(&'a [Ast<'a>]),
-    ClassDecl(Token, Token, &'a [Ast<'a>]),
+    Root(&'a [Token], &'a [Ast<'a>]),
+    Import(Token, &'a [Token], Option<Token>),
+    ClassDecl(&'a [Token], Option<Token>, Token, Token, Option<&'a Ast<'a>>, Option<&'a Ast<'a>>, &'a [Ast<'a>]),
+    Field(&'a [Token], Option<Token>, Token, &'a Ast<'a>, Option<&'a Ast<'a>>),
+    FnDecl(&'a [Token], Option<Token>, Token, Token, Option<&'a Ast<'a>>, &'a Ast<'a>, &'a Ast<'a>),
+    Generics(Token, &'a [Ast<'a>], Token),
+    GenericParam(Token, &'a [Ast<'a>]),
+    Signature(Token, &'a [Ast<'a>], Token, Option<&'a Ast<'a>>),
+    Param(Token, &'a Ast<'a>),
+    Closure(Option<Token>, &'a Ast<'a>, &'a Ast<'a>),
+    Block(Token, &'a [Ast<'a>], Token),
+    Let(Token, Token, Option<&'a Ast<'a>>, Option<&'a Ast<'a>>),
+    If(Token, &'a Ast<'a>, &'a Ast<'a>, Option<&'a Ast<'a>>),
+    While(Token, &'a Ast<'a>, &'a Ast<'a>),
+    For(Token, Token, &'a Ast<'a>, &'a Ast<'a>),
+    Return(Token, Option<&'a Ast<'a>>),
+    Break(Token),
+    Continue(Token),
+    Match(Token, &'a Ast<'a>, Token, &'a [Ast<'a>], Token),
+    Arm(&'a Ast<'a>, Option<&'a Ast<'a>>, Token, &'a Ast<'a>),
+    Wildcard(Token),
+    Bind(Token),
+    StructPattern(&'a Ast<'a>, Token, &'a [Ast<'a>], Token),
+    FieldPattern(Token, Option<&'a Ast<'a>>),
+    Rest(Token),
+    TuplePattern(Token, &'a [Ast<'a>], Token),
+    OrPattern(&'a [Ast<'a>]),
+    RangePattern(&'a Ast<'a>, Token, &'a Ast<'a>),
     Name(Token),
+    Path(&'a Ast<'a>, Token, Token),
+    Generic(&'a Ast<'a>, Token, &'a [Ast<'a>], Token),
+    ArrayType(Token, &'a Ast<'a>, Token),
+    OptionalType(&'a Ast<'a>, Token),
+    TupleType(Token, &'a [Ast<'a>], Token),
+    FnType(Token, Token, &'a [Ast<'a>], Token, Option<&'a Ast<'a>>),
+    Literal(Token),
+    Paren(Token, &'a Ast<'a>, Token),
+    Call(&'a Ast<'a>, Token, &'a [Ast<'a>], Token),
+    Member(&'a Ast<'a>, Token, Token),
+    Index(&'a Ast<'a>, Token, &'a Ast<'a>, Token),
+    Unop(Token, &'a Ast<'a>),
     Binop(Token, &'a Ast<'a>, &'a Ast<'a>),
 }
 
-impl Ast<'_> {
+impl<'a> Ast<'a> {
     pub fn error(token: Token) -> Ast<'static> {
         Ast::Error(token)
     }
+
+    pub fn span(&self) -> SourceSpan {
+        match (self.first_span(), self.last_span()) {
+            (Some(first), Some(last)) => first.to(last),
+            (Some(span), None) | (None, Some(span)) => span,
+            (None, None) => SourceSpan::unknown(),
+        }
+    }
+
+    // Tokens synthesized during error recovery have no location.
+    fn known(token: &Token) -> Option<SourceSpan> {
+        Some(token.span).filter(|span| !span.is_unknown())
+    }
+
+    fn first_span(&self) -> Option<SourceSpan> {
+        let known = Ast::known;
+        match self {
+            Ast::Error(token)
+            | Ast::Import(token, ..)
+            | Ast::Generics(token, ..)
+            | Ast::GenericParam(token, _)
+            | Ast::Signature(token, ..)
+            | Ast::Param(token, _)
+            | Ast::Block(token, ..)
+            | Ast::Let(token, ..)
+            | Ast::If(token, ..)
+            | Ast::While(token, ..)
+            | Ast::For(token, ..)
+            | Ast::Return(token, _)
+            | Ast::Break(token)
+            | Ast::Continue(token)
+            | Ast::Match(token, ..)
+            | Ast::Wildcard(token)
+            | Ast::Bind(token)
+            | Ast::FieldPattern(token, _)
+            | Ast::Rest(token)
+            | Ast::TuplePattern(token, ..)
+            | Ast::Name(token)
+            | Ast::ArrayType(token, ..)
+            | Ast::TupleType(token, ..)
+            | Ast::FnType(token, ..)
+            | Ast::Literal(token)
+            | Ast::Paren(token, ..)
+            | Ast::Unop(token, _) => known(token),
+            Ast::Root(docs, decls) => docs.first().and_then(known).or_else(|| decls.iter().find_map(|d| d.first_span())),
+            Ast::ClassDecl(docs, vis, kw, ..) | Ast::FnDecl(docs, vis, kw, ..) => docs.first().or(vis.as_ref()).and_then(known).or_else(|| known(kw)),
+            Ast::Field(docs, vis, name, ..) => docs.first().or(vis.as_ref()).and_then(known).or_else(|| known(name)),
+            Ast::Closure(kw, sig, _) => kw.as_ref().and_then(known).or_else(|| sig.first_span()),
+            Ast::OrPattern(alts) => alts.first().and_then(|a| a.first_span()),
+            Ast::Arm(ast, ..)
+            | Ast::StructPattern(ast, ..)
+            | Ast::RangePattern(ast, ..)
+            | Ast::Path(ast, ..)
+            | Ast::Generic(ast, ..)
+            | Ast::OptionalType(ast, _)
+            | Ast::Call(ast, ..)
+            | Ast::Member(ast, ..)
+            | Ast::Index(ast, ..)
+            | Ast::Binop(_, ast, _) => ast.first_span(),
+        }
+    }
+
+    fn last_span(&self) -> Option<SourceSpan> {
+        let known = Ast::known;
+        let last = |asts: &[Ast]| asts.iter().rev().find_map(|a| a.last_span());
+        match self {
+            Ast::Error(token)
+            | Ast::Break(token)
+            | Ast::Continue(token)
+            | Ast::Wildcard(token)
+            | Ast::Bind(token)
+            | Ast::Rest(token)
+            | Ast::Name(token)
+            | Ast::Literal(token)
+            | Ast::Path(_, _, token)
+            | Ast::Member(_, _, token)
+            | Ast::OptionalType(_, token) => known(token),
+            Ast::Root(docs, decls) => last(decls).or_else(|| docs.last().and_then(known)),
+            Ast::Import(kw, path, source) => source.as_ref().or(path.last()).and_then(known).or_else(|| known(kw)),
+            Ast::ClassDecl(_, _, kw, name, generics, base, decls) => last(decls)
+                .or_else(|| base.or(*generics).and_then(|a| a.last_span()))
+                .or_else(|| known(name))
+                .or_else(|| known(kw)),
+            Ast::Field(_, _, name, ty, init) => init.unwrap_or(ty).last_span().or_else(|| known(name)),
+            Ast::FnDecl(_, _, kw, _, _, _, body) => body.last_span().or_else(|| known(kw)),
+            Ast::Generics(open, params, close)
+            | Ast::Block(open, params, close)
+            | Ast::TuplePattern(open, params, close)
+            | Ast::TupleType(open, params, close) => known(close).or_else(|| last(params)).or_else(|| known(open)),
+            Ast::Match(kw, scrutinee, _, arms, close) => known(close).or_else(|| last(arms)).or_else(|| scrutinee.last_span()).or_else(|| known(kw)),
+            Ast::StructPattern(base, _, fields, close) | Ast::Call(base, _, fields, close) | Ast::Generic(base, _, fields, close) => {
+                known(close).or_else(|| last(fields)).or_else(|| base.last_span())
+            }
+            Ast::GenericParam(name, bounds) => last(bounds).or_else(|| known(name)),
+            Ast::Signature(open, params, close, ret) | Ast::FnType(open, _, params, close, ret) => ret
+                .and_then(|r| r.last_span())
+                .or_else(|| known(close))
+                .or_else(|| last(params))
+                .or_else(|| known(open)),
+            Ast::Param(name, ty) => ty.last_span().or_else(|| known(name)),
+            Ast::Let(kw, name, ty, init) => init.or(*ty).and_then(|a| a.last_span()).or_else(|| known(name)).or_else(|| known(kw)),
+            Ast::If(_, _, then, otherwise) => otherwise.unwrap_or(then).last_span(),
+            Ast::Return(kw, value) => value.and_then(|v| v.last_span()).or_else(|| known(kw)),
+            Ast::FieldPattern(name, pattern) => pattern.and_then(|p| p.last_span()).or_else(|| known(name)),
+            Ast::OrPattern(alts) => last(alts),
+            Ast::ArrayType(_, ast, close) | Ast::Paren(_, ast, close) | Ast::Index(_, _, ast, close) => {
+                known(close).or_else(|| ast.last_span())
+            }
+            Ast::While(_, _, body)
+            | Ast::For(_, _, _, body)
+            | Ast::Closure(_, _, body)
+            | Ast::Arm(_, _, _, body)
+            | Ast::RangePattern(_, _, body)
+            | Ast::Unop(_, body)
+            | Ast::Binop(_, _, body) => body.last_span(),
+        }
+    }
+}
+
+/// Walks an `Ast` by syntactic role. Hooks are per role rather than per node
+/// kind because a kind's meaning depends on where it appears: `a::b` and `f<T>`
+/// are expressions or types, `(x)` is an expression, a type or a pattern. A pass
+/// overrides the roles it cares about, matches the kinds it handles there and
+/// hands everything else to the matching `walk_*` function, so it keeps up with
+/// new node kinds. Every node is visited, and a node that a `walk_*` function
+/// hands on to another role (a call used as a statement) is visited by both.
+pub trait Visitor<'a> {
+    fn visit_root(&mut self, ast: &'a Ast<'a>) {
+        walk_root(self, ast)
+    }
+    fn visit_decl(&mut self, ast: &'a Ast<'a>) {
+        walk_decl(self, ast)
+    }
+    fn visit_generics(&mut self, ast: &'a Ast<'a>) {
+        walk_generics(self, ast)
+    }
+    fn visit_generic_param(&mut self, ast: &'a Ast<'a>) {
+        walk_generic_param(self, ast)
+    }
+    fn visit_signature(&mut self, ast: &'a Ast<'a>) {
+        walk_signature(self, ast)
+    }
+    fn visit_param(&mut self, ast: &'a Ast<'a>) {
+        walk_param(self, ast)
+    }
+    fn visit_stmt(&mut self, ast: &'a Ast<'a>) {
+        walk_stmt(self, ast)
+    }
+    fn visit_expr(&mut self, ast: &'a Ast<'a>) {
+        walk_expr(self, ast)
+    }
+    fn visit_arm(&mut self, ast: &'a Ast<'a>) {
+        walk_arm(self, ast)
+    }
+    fn visit_pattern(&mut self, ast: &'a Ast<'a>) {
+        walk_pattern(self, ast)
+    }
+    fn visit_type(&mut self, ast: &'a Ast<'a>) {
+        walk_type(self, ast)
+    }
+}
+
+// Visits a node that turned up outside its usual role by the role that owns it.
+// The match is exhaustive so that new node kinds have to be given a role.
+fn walk_other<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ast: &'a Ast<'a>) {
+    match ast {
+        Ast::Error(_)
+        | Ast::Name(_)
+        | Ast::Literal(_)
+        | Ast::Break(_)
+        | Ast::Continue(_)
+        | Ast::Wildcard(_)
+        | Ast::Bind(_)
+        | Ast::Rest(_) => {}
+        Ast::Root(..) => v.visit_root(ast),
+        Ast::Import(..) | Ast::ClassDecl(..) | Ast::Field(..) | Ast::FnDecl(..) => v.visit_decl(ast),
+        Ast::Generics(..) => v.visit_generics(ast),
+        Ast::GenericParam(..) => v.visit_generic_param(ast),
+        Ast::Signature(..) => v.visit_signature(ast),
+        Ast::Param(..) => v.visit_param(ast),
+        Ast::Let(..) | Ast::If(..) | Ast::While(..) | Ast::For(..) | Ast::Return(..) => v.visit_stmt(ast),
+        Ast::Arm(..) => v.visit_arm(ast),
+        Ast::StructPattern(..) | Ast::FieldPattern(..) | Ast::TuplePattern(..) | Ast::OrPattern(..) | Ast::RangePattern(..) => {
+            v.visit_pattern(ast)
+        }
+        Ast::ArrayType(..) | Ast::OptionalType(..) | Ast::TupleType(..) | Ast::FnType(..) => v.visit_type(ast),
+        Ast::Paren(..)
+        | Ast::Unop(..)
+        | Ast::Binop(..)
+        | Ast::Member(..)
+        | Ast::Path(..)
+        | Ast::Index(..)
+        | Ast::Call(..)
+        | Ast::Generic(..)
+        | Ast::Match(..)
+        | Ast::Closure(..)
+        | Ast::Block(..) => v.visit_expr(ast),
+    }
+}
+
+pub fn walk_root<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ast: &'a Ast<'a>) {
+    match ast {
+        Ast::Root(_, decls) => decls.iter().for_each(|d| v.visit_decl(d)),
+        _ => v.visit_decl(ast),
+    }
+}
+
+pub fn walk_decl<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ast: &'a Ast<'a>) {
+    match ast {
+        Ast::Error(_) | Ast::Import(..) => {}
+        Ast::ClassDecl(_, _, _, _, generics, base, decls) => {
+            generics.iter().for_each(|g| v.visit_generics(g));
+            base.iter().for_each(|b| v.visit_type(b));
+            decls.iter().for_each(|d| v.visit_decl(d));
+        }
+        Ast::Field(_, _, _, ty, init) => {
+            v.visit_type(ty);
+            init.iter().for_each(|i| v.visit_expr(i));
+        }
+        Ast::FnDecl(_, _, _, _, generics, sig, body) => {
+            generics.iter().for_each(|g| v.visit_generics(g));
+            v.visit_signature(sig);
+            v.visit_stmt(body);
+        }
+        // Top-level expressions.
+        _ => v.visit_expr(ast),
+    }
+}
+
+pub fn walk_generics<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ast: &'a Ast<'a>) {
+    match ast {
+        Ast::Generics(_, params, _) => params.iter().for_each(|p| v.visit_generic_param(p)),
+        _ => walk_other(v, ast),
+    }
+}
+
+pub fn walk_generic_param<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ast: &'a Ast<'a>) {
+    match ast {
+        Ast::GenericParam(_, bounds) => bounds.iter().for_each(|b| v.visit_type(b)),
+        _ => walk_other(v, ast),
+    }
+}
+
+pub fn walk_signature<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ast: &'a Ast<'a>) {
+    match ast {
+        Ast::Signature(_, params, _, ret) => {
+            params.iter().for_each(|p| v.visit_param(p));
+            ret.iter().for_each(|r| v.visit_type(r));
+        }
+        _ => walk_other(v, ast),
+    }
+}
+
+pub fn walk_param<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ast: &'a Ast<'a>) {
+    match ast {
+        Ast::Param(_, ty) => v.visit_type(ty),
+        _ => walk_other(v, ast),
+    }
+}
+
+pub fn walk_stmt<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ast: &'a Ast<'a>) {
+    match ast {
+        Ast::Error(_) | Ast::Break(_) | Ast::Continue(_) => {}
+        Ast::Block(_, items, _) => items.iter().for_each(|i| v.visit_stmt(i)),
+        Ast::Let(_, _, ty, init) => {
+            ty.iter().for_each(|t| v.visit_type(t));
+            init.iter().for_each(|i| v.visit_expr(i));
+        }
+        Ast::If(_, cond, then, otherwise) => {
+            v.visit_expr(cond);
+            v.visit_stmt(then);
+            otherwise.iter().for_each(|o| v.visit_stmt(o));
+        }
+        Ast::While(_, cond, body) | Ast::For(_, _, cond, body) => {
+            v.visit_expr(cond);
+            v.visit_stmt(body);
+        }
+        Ast::Return(_, value) => value.iter().for_each(|e| v.visit_expr(e)),
+        _ => v.visit_expr(ast),
+    }
+}
+
+pub fn walk_expr<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ast: &'a Ast<'a>) {
+    match ast {
+        Ast::Paren(_, e, _) | Ast::Unop(_, e) | Ast::Member(e, _, _) | Ast::Path(e, _, _) => v.visit_expr(e),
+        Ast::Binop(_, lhs, rhs) | Ast::Index(lhs, _, rhs, _) => {
+            v.visit_expr(lhs);
+            v.visit_expr(rhs);
+        }
+        Ast::Call(callee, _, args, _) => {
+            v.visit_expr(callee);
+            args.iter().for_each(|a| v.visit_expr(a));
+        }
+        Ast::Generic(base, _, args, _) => {
+            v.visit_expr(base);
+            args.iter().for_each(|a| v.visit_type(a));
+        }
+        Ast::Match(_, scrutinee, _, arms, _) => {
+            v.visit_expr(scrutinee);
+            arms.iter().for_each(|a| v.visit_arm(a));
+        }
+        Ast::Closure(_, sig, body) => {
+            v.visit_signature(sig);
+            v.visit_expr(body);
+        }
+        Ast::Block(_, items, _) => items.iter().for_each(|i| v.visit_stmt(i)),
+        _ => walk_other(v, ast),
+    }
+}
+
+pub fn walk_arm<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ast: &'a Ast<'a>) {
+    match ast {
+        Ast::Arm(pattern, guard, _, body) => {
+            v.visit_pattern(pattern);
+            guard.iter().for_each(|g| v.visit_expr(g));
+            v.visit_expr(body);
+        }
+        _ => walk_other(v, ast),
+    }
+}
+
+pub fn walk_pattern<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ast: &'a Ast<'a>) {
+    match ast {
+        Ast::StructPattern(base, _, fields, _) => {
+            v.visit_expr(base);
+            fields.iter().for_each(|f| v.visit_pattern(f));
+        }
+        Ast::FieldPattern(_, pattern) => pattern.iter().for_each(|p| v.visit_pattern(p)),
+        Ast::TuplePattern(_, items, _) | Ast::OrPattern(items) => items.iter().for_each(|i| v.visit_pattern(i)),
+        Ast::RangePattern(lo, _, hi) => {
+            v.visit_pattern(lo);
+            v.visit_pattern(hi);
+        }
+        Ast::Paren(_, inner, _) => v.visit_pattern(inner),
+        _ => walk_other(v, ast),
+    }
+}
+
+pub fn walk_type<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ast: &'a Ast<'a>) {
+    match ast {
+        Ast::Path(base, _, _) | Ast::ArrayType(_, base, _) | Ast::OptionalType(base, _) => v.visit_type(base),
+        Ast::Generic(base, _, args, _) => {
+            v.visit_type(base);
+            args.iter().for_each(|a| v.visit_type(a));
+        }
+        Ast::TupleType(_, types, _) => types.iter().for_each(|t| v.visit_type(t)),
+        Ast::Paren(_, inner, _) => v.visit_type(inner),
+        Ast::FnType(_, _, params, _, ret) => {
+            params.iter().for_each(|p| v.visit_type(p));
+            ret.iter().for_each(|r| v.visit_type(r));
+        }
+        _ => walk_other(v, ast),
+    }
+}
+
+/// Rebuilds an `Ast` into a new arena, by the same roles as `Visitor`. The
+/// defaults copy each node with its children folded, so a rewrite only
+/// overrides the roles it changes.
+pub trait Folder<'a, 'b> {
+    fn bump(&self) -> &'b Bump;
+
+    fn fold_root(&mut self, ast: &'a Ast<'a>) -> Ast<'b> {
+        walk_fold_root(self, ast)
+    }
+    fn fold_decl(&mut self, ast: &'a Ast<'a>) -> Ast<'b> {
+        walk_fold_decl(self, ast)
+    }
+    fn fold_generics(&mut self, ast: &'a Ast<'a>) -> Ast<'b> {
+        walk_fold_generics(self, ast)
+    }
+    fn fold_generic_param(&mut self, ast: &'a Ast<'a>) -> Ast<'b> {
+        walk_fold_generic_param(self, ast)
+    }
+    fn fold_signature(&mut self, ast: &'a Ast<'a>) -> Ast<'b> {
+        walk_fold_signature(self, ast)
+    }
+    fn fold_param(&mut self, ast: &'a Ast<'a>) -> Ast<'b> {
+        walk_fold_param(self, ast)
+    }
+    fn fold_stmt(&mut self, ast: &'a Ast<'a>) -> Ast<'b> {
+        walk_fold_stmt(self, ast)
+    }
+    fn fold_expr(&mut self, ast: &'a Ast<'a>) -> Ast<'b> {
+        walk_fold_expr(self, ast)
+    }
+    fn fold_arm(&mut self, ast: &'a Ast<'a>) -> Ast<'b> {
+        walk_fold_arm(self, ast)
+    }
+    fn fold_pattern(&mut self, ast: &'a Ast<'a>) -> Ast<'b> {
+        walk_fold_pattern(self, ast)
+    }
+    fn fold_type(&mut self, ast: &'a Ast<'a>) -> Ast<'b> {
+        walk_fold_type(self, ast)
+    }
+}
+
+fn fold_one<'a, 'b, F: Folder<'a, 'b> + ?Sized>(
+    f: &mut F,
+    ast: &'a Ast<'a>,
+    fold: fn(&mut F, &'a Ast<'a>) -> Ast<'b>,
+) -> &'b Ast<'b> {
+    let ast = fold(f, ast);
+    f.bump().alloc(ast)
+}
+
+fn fold_opt<'a, 'b, F: Folder<'a, 'b> + ?Sized>(
+    f: &mut F,
+    ast: Option<&'a Ast<'a>>,
+    fold: fn(&mut F, &'a Ast<'a>) -> Ast<'b>,
+) -> Option<&'b Ast<'b>> {
+    ast.map(|ast| fold_one(f, ast, fold))
+}
+
+fn fold_all<'a, 'b, F: Folder<'a, 'b> + ?Sized>(
+    f: &mut F,
+    asts: &'a [Ast<'a>],
+    fold: fn(&mut F, &'a Ast<'a>) -> Ast<'b>,
+) -> &'b [Ast<'b>] {
+    let bump = f.bump();
+    bump.alloc_slice_fill_iter(asts.iter().map(|ast| fold(f, ast)))
+}
+
+fn fold_tokens<'b>(bump: &'b Bump, tokens: &[Token]) -> &'b [Token] {
+    bump.alloc_slice_clone(tokens)
+}
+
+// Folds a node that turned up outside its usual role by the role that owns it.
+// The match is exhaustive so that new node kinds have to be given a role.
+fn fold_other<'a, 'b, F: Folder<'a, 'b> + ?Sized>(f: &mut F, ast: &'a Ast<'a>) -> Ast<'b> {
+    match ast {
+        Ast::Error(t) => Ast::Error(t.clone()),
+        Ast::Name(t) => Ast::Name(t.clone()),
+        Ast::Literal(t) => Ast::Literal(t.clone()),
+        Ast::Break(t) => Ast::Break(t.clone()),
+        Ast::Continue(t) => Ast::Continue(t.clone()),
+        Ast::Wildcard(t) => Ast::Wildcard(t.clone()),
+        Ast::Bind(t) => Ast::Bind(t.clone()),
+        Ast::Rest(t) => Ast::Rest(t.clone()),
+        Ast::Root(..) => walk_fold_root(f, ast),
+        Ast::Import(..) | Ast::ClassDecl(..) | Ast::Field(..) | Ast::FnDecl(..) => walk_fold_decl(f, ast),
+        Ast::Generics(..) => walk_fold_generics(f, ast),
+        Ast::GenericParam(..) => walk_fold_generic_param(f, ast),
+        Ast::Signature(..) => walk_fold_signature(f, ast),
+        Ast::Param(..) => walk_fold_param(f, ast),
+        Ast::Let(..) | Ast::If(..) | Ast::While(..) | Ast::For(..) | Ast::Return(..) => walk_fold_stmt(f, ast),
+        Ast::Arm(..) => walk_fold_arm(f, ast),
+        Ast::StructPattern(..) | Ast::FieldPattern(..) | Ast::TuplePattern(..) | Ast::OrPattern(..) | Ast::RangePattern(..) => {
+            walk_fold_pattern(f, ast)
+        }
+        Ast::ArrayType(..) | Ast::OptionalType(..) | Ast::TupleType(..) | Ast::FnType(..) => walk_fold_type(f, ast),
+        Ast::Paren(..)
+        | Ast::Unop(..)
+        | Ast::Binop(..)
+        | Ast::Member(..)
+        | Ast::Path(..)
+        | Ast::Index(..)
+        | Ast::Call(..)
+        | Ast::Generic(..)
+        | Ast::Match(..)
+        | Ast::Closure(..)
+        | Ast::Block(..) => walk_fold_expr(f, ast),
+    }
+}
+
+pub fn walk_fold_root<'a, 'b, F: Folder<'a, 'b> + ?Sized>(f: &mut F, ast: &'a Ast<'a>) -> Ast<'b> {
+    match ast {
+        Ast::Root(docs, decls) => Ast::Root(fold_tokens(f.bump(), docs), fold_all(f, decls, F::fold_decl)),
+        _ => f.fold_decl(ast),
+    }
+}
+
+pub fn walk_fold_decl<'a, 'b, F: Folder<'a, 'b> + ?Sized>(f: &mut F, ast: &'a Ast<'a>) -> Ast<'b> {
+    let bump = f.bump();
+    match ast {
+        Ast::Import(kw, path, source) => Ast::Import(kw.clone(), fold_tokens(bump, path), source.clone()),
+        Ast::ClassDecl(docs, vis, kw, name, generics, base, decls) => Ast::ClassDecl(
+            fold_tokens(bump, docs),
+            vis.clone(),
+            kw.clone(),
+            name.clone(),
+            fold_opt(f, *generics, F::fold_generics),
+            fold_opt(f, *base, F::fold_type),
+            fold_all(f, decls, F::fold_decl),
+        ),
+        Ast::Field(docs, vis, name, ty, init) => Ast::Field(
+            fold_tokens(bump, docs),
+            vis.clone(),
+            name.clone(),
+            fold_one(f, ty, F::fold_type),
+            fold_opt(f, *init, F::fold_expr),
+        ),
+        Ast::FnDecl(docs, vis, kw, name, generics, sig, body) => Ast::FnDecl(
+            fold_tokens(bump, docs),
+            vis.clone(),
+            kw.clone(),
+            name.clone(),
+            fold_opt(f, *generics, F::fold_generics),
+            fold_one(f, sig, F::fold_signature),
+            fold_one(f, body, F::fold_stmt),
+        ),
+        Ast::Error(_) => fold_other(f, ast),
+        _ => f.fold_expr(ast),
+    }
+}
+
+pub fn walk_fold_generics<'a, 'b, F: Folder<'a, 'b> + ?Sized>(f: &mut F, ast: &'a Ast<'a>) -> Ast<'b> {
+    match ast {
+        Ast::Generics(open, params, close) => Ast::Generics(open.clone(), fold_all(f, params, F::fold_generic_param), close.clone()),
+        _ => fold_other(f, ast),
+    }
+}
+
+pub fn walk_fold_generic_param<'a, 'b, F: Folder<'a, 'b> + ?Sized>(f: &mut F, ast: &'a Ast<'a>) -> Ast<'b> {
+    match ast {
+        Ast::GenericParam(name, bounds) => Ast::GenericParam(name.clone(), fold_all(f, bounds, F::fold_type)),
+        _ => fold_other(f, ast),
+    }
+}
+
+pub fn walk_fold_signature<'a, 'b, F: Folder<'a, 'b> + ?Sized>(f: &mut F, ast: &'a Ast<'a>) -> Ast<'b> {
+    match ast {
+        Ast::Signature(open, params, close, ret) => Ast::Signature(
+            open.clone(),
+            fold_all(f, params, F::fold_param),
+            close.clone(),
+            fold_opt(f, *ret, F::fold_type),
+        ),
+        _ => fold_other(f, ast),
+    }
+}
+
+pub fn walk_fold_param<'a, 'b, F: Folder<'a, 'b> + ?Sized>(f: &mut F, ast: &'a Ast<'a>) -> Ast<'b> {
+    match ast {
+        Ast::Param(name, ty) => Ast::Param(name.clone(), fold_one(f, ty, F::fold_type)),
+        _ => fold_other(f, ast),
+    }
+}
+
+pub fn walk_fold_stmt<'a, 'b, F: Folder<'a, 'b> + ?Sized>(f: &mut F, ast: &'a Ast<'a>) -> Ast<'b> {
+    match ast {
+        Ast::Block(open, items, close) => Ast::Block(open.clone(), fold_all(f, items, F::fold_stmt), close.clone()),
+        Ast::Let(kw, name, ty, init) => Ast::Let(
+            kw.clone(),
+            name.clone(),
+            fold_opt(f, *ty, F::fold_type),
+            fold_opt(f, *init, F::fold_expr),
+        ),
+        Ast::If(kw, cond, then, otherwise) => Ast::If(
+            kw.clone(),
+            fold_one(f, cond, F::fold_expr),
+            fold_one(f, then, F::fold_stmt),
+            fold_opt(f, *otherwise, F::fold_stmt),
+        ),
+        Ast::While(kw, cond, body) => Ast::While(kw.clone(), fold_one(f, cond, F::fold_expr), fold_one(f, body, F::fold_stmt)),
+        Ast::For(kw, name, iter, body) => Ast::For(
+            kw.clone(),
+            name.clone(),
+            fold_one(f, iter, F::fold_expr),
+            fold_one(f, body, F::fold_stmt),
+        ),
+        Ast::Return(kw, value) => Ast::Return(kw.clone(), fold_opt(f, *value, F::fold_expr)),
+        _ => f.fold_expr(ast),
+    }
+}
+
+pub fn walk_fold_expr<'a, 'b, F: Folder<'a, 'b> + ?Sized>(f: &mut F, ast: &'a Ast<'a>) -> Ast<'b> {
+    match ast {
+        Ast::Paren(open, e, close) => Ast::Paren(open.clone(), fold_one(f, e, F::fold_expr), close.clone()),
+        Ast::Unop(op, e) => Ast::Unop(op.clone(), fold_one(f, e, F::fold_expr)),
+        Ast::Binop(op, lhs, rhs) => Ast::Binop(op.clone(), fold_one(f, lhs, F::fold_expr), fold_one(f, rhs, F::fold_expr)),
+        Ast::Member(e, dot, name) => Ast::Member(fold_one(f, e, F::fold_expr), dot.clone(), name.clone()),
+        Ast::Path(e, sep, name) => Ast::Path(fold_one(f, e, F::fold_expr), sep.clone(), name.clone()),
+        Ast::Index(e, open, index, close) => Ast::Index(
+            fold_one(f, e, F::fold_expr),
+            open.clone(),
+            fold_one(f, index, F::fold_expr),
+            close.clone(),
+        ),
+        Ast::Call(callee, open, args, close) => Ast::Call(
+            fold_one(f, callee, F::fold_expr),
+            open.clone(),
+            fold_all(f, args, F::fold_expr),
+            close.clone(),
+        ),
+        Ast::Generic(base, open, args, close) => Ast::Generic(
+            fold_one(f, base, F::fold_expr),
+            open.clone(),
+            fold_all(f, args, F::fold_type),
+            close.clone(),
+        ),
+        Ast::Match(kw, scrutinee, open, arms, close) => Ast::Match(
+            kw.clone(),
+            fold_one(f, scrutinee, F::fold_expr),
+            open.clone(),
+            fold_all(f, arms, F::fold_arm),
+            close.clone(),
+        ),
+        Ast::Closure(kw, sig, body) => Ast::Closure(kw.clone(), fold_one(f, sig, F::fold_signature), fold_one(f, body, F::fold_expr)),
+        Ast::Block(open, items, close) => Ast::Block(open.clone(), fold_all(f, items, F::fold_stmt), close.clone()),
+        _ => fold_other(f, ast),
+    }
+}
+
+pub fn walk_fold_arm<'a, 'b, F: Folder<'a, 'b> + ?Sized>(f: &mut F, ast: &'a Ast<'a>) -> Ast<'b> {
+    match ast {
+        Ast::Arm(pattern, guard, arrow, body) => Ast::Arm(
+            fold_one(f, pattern, F::fold_pattern),
+            fold_opt(f, *guard, F::fold_expr),
+            arrow.clone(),
+            fold_one(f, body, F::fold_expr),
+        ),
+        _ => fold_other(f, ast),
+    }
+}
+
+pub fn walk_fold_pattern<'a, 'b, F: Folder<'a, 'b> + ?Sized>(f: &mut F, ast: &'a Ast<'a>) -> Ast<'b> {
+    match ast {
+        Ast::StructPattern(base, open, fields, close) => Ast::StructPattern(
+            fold_one(f, base, F::fold_expr),
+            open.clone(),
+            fold_all(f, fields, F::fold_pattern),
+            close.clone(),
+        ),
+        Ast::FieldPattern(name, pattern) => Ast::FieldPattern(name.clone(), fold_opt(f, *pattern, F::fold_pattern)),
+        Ast::TuplePattern(open, items, close) => Ast::TuplePattern(open.clone(), fold_all(f, items, F::fold_pattern), close.clone()),
+        Ast::OrPattern(alts) => Ast::OrPattern(fold_all(f, alts, F::fold_pattern)),
+        Ast::RangePattern(lo, op, hi) => Ast::RangePattern(fold_one(f, lo, F::fold_pattern), op.clone(), fold_one(f, hi, F::fold_pattern)),
+        Ast::Paren(open, inner, close) => Ast::Paren(open.clone(), fold_one(f, inner, F::fold_pattern), close.clone()),
+        // Paths and literals (including negated ones) are expressions inside patterns.
+        Ast::Path(..) | Ast::Unop(..) => f.fold_expr(ast),
+        _ => fold_other(f, ast),
+    }
+}
+
+pub fn walk_fold_type<'a, 'b, F: Folder<'a, 'b> + ?Sized>(f: &mut F, ast: &'a Ast<'a>) -> Ast<'b> {
+    match ast {
+        Ast::Path(base, sep, name) => Ast::Path(fold_one(f, base, F::fold_type), sep.clone(), name.clone()),
+        Ast::Generic(base, open, args, close) => Ast::Generic(
+            fold_one(f, base, F::fold_type),
+            open.clone(),
+            fold_all(f, args, F::fold_type),
+            close.clone(),
+        ),
+        Ast::ArrayType(open, elem, close) => Ast::ArrayType(open.clone(), fold_one(f, elem, F::fold_type), close.clone()),
+        Ast::OptionalType(ty, question) => Ast::OptionalType(fold_one(f, ty, F::fold_type), question.clone()),
+        Ast::TupleType(open, types, close) => Ast::TupleType(open.clone(), fold_all(f, types, F::fold_type), close.clone()),
+        Ast::Paren(open, inner, close) => Ast::Paren(open.clone(), fold_one(f, inner, F::fold_type), close.clone()),
+        Ast::FnType(kw, open, params, close, ret) => Ast::FnType(
+            kw.clone(),
+            open.clone(),
+            fold_all(f, params, F::fold_type),
+            close.clone(),
+            fold_opt(f, *ret, F::fold_type),
+        ),
+        _ => fold_other(f, ast),
+    }
 }
 
 self_cell!(
@@ -84,36 +1023,447 @@ impl AstRoot {
         AstRoot { cell: AstCell::new(bump, f) }
     }
 
-    pub fn root<'a>(&'a self) -> Ast<'a> {
-        self.cell.borrow_dependent().clone()
+    pub fn root<'a>(&'a self) -> &'a Ast<'a> {
+        self.cell.borrow_dependent()
+    }
+}
+
+index_type!(NodeId);
+
+#[derive(Clone, Copy, Debug)]
+struct NodeInfo {
+    span: SourceSpan,
+    parent: Option<NodeId>,
+}
+
+struct NodeCollector<'m> {
+    table: &'m mut NodeTable,
+    ids: &'m mut HashMap<usize, usize>,
+    parent: Option<usize>,
+}
+
+impl<'m> NodeCollector<'m> {
+    fn add<'a>(&mut self, ast: &'a Ast<'a>, walk: fn(&mut Self, &'a Ast<'a>)) {
+        let address = ast as *const Ast as usize;
+        // A node handed on to another role is already numbered.
+        if self.parent.is_some_and(|parent| self.ids.get(&address) == Some(&parent)) {
+            return walk(self, ast);
+        }
+        let index = self.table.nodes.len();
+        self.ids.insert(address, index);
+        self.table.nodes.push(NodeInfo {
+            span: ast.span(),
+            parent: self.parent.map(NodeId::new),
+        });
+        let parent = self.parent.replace(index);
+        walk(self, ast);
+        self.parent = parent;
+    }
+}
+
+impl<'m, 'a> Visitor<'a> for NodeCollector<'m> {
+    fn visit_root(&mut self, ast: &'a Ast<'a>) {
+        self.add(ast, walk_root)
+    }
+    fn visit_decl(&mut self, ast: &'a Ast<'a>) {
+        self.add(ast, walk_decl)
+    }
+    fn visit_generics(&mut self, ast: &'a Ast<'a>) {
+        self.add(ast, walk_generics)
+    }
+    fn visit_generic_param(&mut self, ast: &'a Ast<'a>) {
+        self.add(ast, walk_generic_param)
+    }
+    fn visit_signature(&mut self, ast: &'a Ast<'a>) {
+        self.add(ast, walk_signature)
+    }
+    fn visit_param(&mut self, ast: &'a Ast<'a>) {
+        self.add(ast, walk_param)
+    }
+    fn visit_stmt(&mut self, ast: &'a Ast<'a>) {
+        self.add(ast, walk_stmt)
+    }
+    fn visit_expr(&mut self, ast: &'a Ast<'a>) {
+        self.add(ast, walk_expr)
+    }
+    fn visit_arm(&mut self, ast: &'a Ast<'a>) {
+        self.add(ast, walk_arm)
+    }
+    fn visit_pattern(&mut self, ast: &'a Ast<'a>) {
+        self.add(ast, walk_pattern)
+    }
+    fn visit_type(&mut self, ast: &'a Ast<'a>) {
+        self.add(ast, walk_type)
+    }
+}
+
+/// Spans and parents of a tree's nodes, indexed by `NodeId`. It holds nothing
+/// that points into the tree, so it stays valid after the tree is dropped.
+pub struct NodeTable {
+    first: usize,
+    nodes: Vec<NodeInfo>,
+}
+
+impl NodeTable {
+    // Moves every id by `first`, so tables from different files don't overlap.
+    fn rebase(&mut self, first: usize) {
+        let delta = first - self.first;
+        self.first = first;
+        for node in &mut self.nodes {
+            node.parent = node.parent.map(|p| NodeId::new(p.index() + delta));
+        }
+    }
+
+    fn info(&self, id: NodeId) -> Option<&NodeInfo> {
+        id.index().checked_sub(self.first).and_then(|index| self.nodes.get(index))
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.info(id).is_some()
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId::new(self.first)
+    }
+
+    pub fn span(&self, id: NodeId) -> Option<SourceSpan> {
+        self.info(id).map(|info| info.span)
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.info(id).and_then(|info| info.parent)
+    }
+
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(self.parent(id), |&id| self.parent(id))
+    }
+}
+
+/// Gives every node of an `AstRoot` a `NodeId` in pre-order. Nodes live in the
+/// tree's arena and never move, so they are looked up by address, and the map
+/// borrows the tree so that no other tree can reuse those addresses meanwhile.
+/// A map from `Compiler::add_nodes` unregisters its table when dropped, so node
+/// ids must be reported while the map is alive.
+pub struct NodeMap<'a> {
+    table: Arc<NodeTable>,
+    ids: HashMap<usize, usize>,
+    tree: &'a AstRoot,
+    compiler: Option<&'a Compiler>,
+}
+
+impl<'a> NodeMap<'a> {
+    pub fn new(tree: &'a AstRoot) -> NodeMap<'a> {
+        let mut table = NodeTable { first: 0, nodes: Vec::new() };
+        let mut ids = HashMap::new();
+        NodeCollector { table: &mut table, ids: &mut ids, parent: None }.visit_root(tree.root());
+        NodeMap {
+            table: Arc::new(table),
+            ids,
+            tree,
+            compiler: None,
+        }
+    }
+
+    pub(crate) fn register(&mut self, compiler: &'a Compiler, first: usize) {
+        Arc::get_mut(&mut self.table).expect("node table registered twice").rebase(first);
+        self.compiler = Some(compiler);
+    }
+
+    pub fn tree(&self) -> &'a AstRoot {
+        self.tree
+    }
+
+    pub fn table(&self) -> &Arc<NodeTable> {
+        &self.table
+    }
+
+    pub fn id(&self, ast: &Ast) -> Option<NodeId> {
+        self.ids.get(&(ast as *const Ast as usize)).map(|&index| NodeId::new(self.table.first + index))
+    }
+
+    /// The id of a node that is known to be part of the tree.
+    pub fn node_id(&self, ast: &Ast) -> NodeId {
+        self.id(ast).expect("node is not part of this tree")
+    }
+}
+
+impl<'a> Drop for NodeMap<'a> {
+    fn drop(&mut self) {
+        if let Some(compiler) = self.compiler {
+            compiler.remove_nodes(self.root());
+        }
+    }
+}
+
+impl<'a> std::ops::Deref for NodeMap<'a> {
+    type Target = NodeTable;
+
+    fn deref(&self) -> &NodeTable {
+        &self.table
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ident {
+    pub name: String,
+    pub span: SourceSpan,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visibility {
+    Private,
+    Public,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mutability {
+    Immutable,
+    Mutable,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClassKind {
+    Class,
+    Struct,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RangeEnd {
+    Exclusive,
+    Inclusive,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    Integer(u64),
+    Float(f64),
+    String(String),
+    Char(char),
+    Bool(bool),
+    Null,
+}
+
+#[derive(Clone, Debug)]
+pub enum TypeKind {
+    Error,
+    Named(Ident),
+    Path(Type, Ident),
+    Generic(Type, Vec<Type>),
+    Array(Type),
+    Optional(Type),
+    Tuple(Vec<Type>),
+    Fn(Vec<Type>, Option<Type>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Type {
+    pub kind: Box<TypeKind>,
+    pub span: SourceSpan,
+}
+
+impl Type {
+    pub fn new(kind: TypeKind, span: SourceSpan) -> Type {
+        Type {
+            kind: Box::new(kind),
+            span,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum PatternKind {
+    Error,
+    Wildcard,
+    Bind(Ident),
+    Literal(Expr),
+    Range(Expr, RangeEnd, Expr),
+    Path(Vec<Ident>),
+    // The flag is set when the field list ends in `..`.
+    Struct(Vec<Ident>, Vec<FieldPattern>, bool),
+    Tuple(Vec<Pattern>),
+    Or(Vec<Pattern>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    pub kind: Box<PatternKind>,
+    pub span: SourceSpan,
+}
+
+impl Pattern {
+    pub fn new(kind: PatternKind, span: SourceSpan) -> Pattern {
+        Pattern {
+            kind: Box::new(kind),
+            span,
+        }
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct FieldPattern {
+    pub name: Ident,
+    pub pattern: Pattern,
+}
+
+#[derive(Clone, Debug)]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+    pub span: SourceSpan,
+}
+
+#[derive(Clone, Debug)]
+pub struct Param {
+    pub name: Ident,
+    pub ty: Option<Type>,
+    pub span: SourceSpan,
+}
+
+#[derive(Clone, Debug)]
+pub struct GenericParam {
+    pub name: Ident,
+    pub bounds: Vec<Type>,
+    pub span: SourceSpan,
+}
+
+#[derive(Clone, Debug)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    pub span: SourceSpan,
+}
+
+#[derive(Clone, Debug)]
+pub struct Closure {
+    pub params: Vec<Param>,
+    pub ret: Option<Type>,
+    pub body: Expr,
+}
+
+#[derive(Clone, Debug)]
 pub enum ExprKind {
     Error,
-    Identifier,
+    Identifier(Ident),
+    Literal(Literal),
+    UnaryOp(UnaryOp, Expr),
     BinaryOp(BinaryOp, Expr, Expr),
+    Call(Expr, Vec<Expr>),
+    Member(Expr, Ident),
+    Index(Expr, Expr),
+    Path(Expr, Ident),
+    Generic(Expr, Vec<Type>),
+    Match(Expr, Vec<Arm>),
+    Closure(Closure),
+    Block(Block),
 }
 
+#[derive(Clone, Debug)]
 pub struct Expr {
     pub kind: Box<ExprKind>,
     pub span: SourceSpan,
 }
 
+impl Expr {
+    pub fn new(kind: ExprKind, span: SourceSpan) -> Expr {
+        Expr {
+            kind: Box::new(kind),
+            span,
+        }
+    }
+    pub fn error(span: SourceSpan) -> Expr {
+        Expr::new(ExprKind::Error, span)
+    }
+}
+
+#[derive(Clone, Debug)]
 pub enum StmtKind {
     Error,
-    If(Expr, Stmt),
+    Expr(Expr),
+    Let(Mutability, Ident, Option<Type>, Option<Expr>),
+    // The else branch is either a block or another `if`.
+    If(Expr, Block, Option<Stmt>),
+    While(Expr, Block),
+    For(Ident, Expr, Block),
+    Return(Option<Expr>),
+    Break,
+    Continue,
+    Block(Block),
 }
 
+#[derive(Clone, Debug)]
 pub struct Stmt {
     pub kind: Box<StmtKind>,
     pub span: SourceSpan,
 }
 
+impl Stmt {
+    pub fn new(kind: StmtKind, span: SourceSpan) -> Stmt {
+        Stmt {
+            kind: Box::new(kind),
+            span,
+        }
+    }
+    pub fn error(span: SourceSpan) -> Stmt {
+        Stmt::new(StmtKind::Error, span)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ImportDecl {
+    pub path: Vec<Ident>,
+    pub source: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ClassDecl {
+    pub docs: Vec<String>,
+    pub vis: Visibility,
+    pub kind: ClassKind,
+    pub name: Ident,
+    pub generics: Vec<GenericParam>,
+    pub base: Option<Type>,
+    pub members: Vec<Decl>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FieldDecl {
+    pub docs: Vec<String>,
+    pub vis: Visibility,
+    pub name: Ident,
+    pub ty: Type,
+    pub init: Option<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FnDecl {
+    pub docs: Vec<String>,
+    pub vis: Visibility,
+    pub name: Ident,
+    pub generics: Vec<GenericParam>,
+    pub params: Vec<Param>,
+    pub ret: Option<Type>,
+    pub body: Block,
+}
+
+#[derive(Clone, Debug)]
 pub enum DeclKind {
     Error,
+    Import(ImportDecl),
+    Class(ClassDecl),
+    Field(FieldDecl),
+    Fn(FnDecl),
+    Expr(Expr),
 }
 
+#[derive(Clone, Debug)]
 pub struct Decl {
     pub kind: Box<DeclKind>,
     pub span: SourceSpan,
@@ -131,10 +1481,18 @@ impl Decl {
     }
 }
 
+#[derive(Clone, Debug)]
 pub struct Root {
+    pub docs: Vec<String>,
     pub decls: Vec<Decl>,
 }
 
+impl Locatable for NodeId {
+    fn source_span(&self, locator: &dyn Locator) -> SourceSpan {
+        locator.node_span(*self)
+    }
+}
+
 impl Locatable for Token {
     fn source_span(&self, _: &dyn Locator) -> SourceSpan {
         self.span
@@ -1,11 +1,13 @@
 use std::{
-    borrow::Cow, cell::OnceCell, collections::HashMap, sync::{
-        atomic::{AtomicUsize, Ordering}, Arc
+    borrow::Cow, collections::{BTreeMap, HashMap}, sync::{
+        atomic::{AtomicUsize, Ordering}, Arc, OnceLock
     }
 };
 
 use dashmap::DashMap;
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
+
+use crate::ast::{AstRoot, NodeId, NodeMap, NodeTable};
 
 macro_rules! error {
     ($errors:ident, $loc:expr, $($msg:tt)+) => {
@@ -20,6 +22,19 @@ macro_rules! error {
     };
 }
 
+macro_rules! warning {
+    ($errors:ident, $loc:expr, $($msg:tt)+) => {
+        {
+            let msg = format!($($msg)+);
+            let int_loc = InternalLocation {
+                file: file!(),
+                line: line!(),
+            };
+            push_warning($errors, &int_loc, $loc, msg);
+        }
+    };
+}
+
 index_type!(SourceFile);
 
 impl SourceFile {
@@ -32,7 +47,7 @@ pub struct SourceFileInfo {
     file: SourceFile,
     name: String,
     data: Vec<u8>,
-    line_breaks: OnceCell<Vec<u32>>,
+    line_breaks: OnceLock<Vec<u32>>,
 }
 
 impl SourceFileInfo {
@@ -49,12 +64,11 @@ impl SourceFileInfo {
     fn line_breaks(&self) -> &[u32] {
         self.line_breaks
             .get_or_init(|| {
-                let line_breaks = self
-                    .data
-                    .iter()
-                    .enumerate()
-                    .filter(|(_, c)| **c == b'\n')
-                    .map(|(ix, _)| (ix + 1) as u32);
+                // Lines end in `\n`, `\r\n` or a lone `\r`.
+                let data = &self.data;
+                let line_breaks = memchr::memchr2_iter(b'\n', b'\r', data)
+                    .filter(|&ix| !(data[ix] == b'\r' && data.get(ix + 1) == Some(&b'\n')))
+                    .map(|ix| (ix + 1) as u32);
                 std::iter::once(0).chain(line_breaks).collect()
             })
             .as_slice()
@@ -74,8 +88,14 @@ impl SourceFileInfo {
             None => 0,
         };
 
-        let prefix = &self.get_line(line as u32)[..line_offset as usize];
-        let col = String::from_utf8_lossy(prefix).chars().count();
+        let mut prefix = &self.get_line(line as u32)[..line_offset as usize];
+        if line == 0 {
+            prefix = prefix.strip_prefix(b"\xef\xbb\xbf".as_slice()).unwrap_or(prefix);
+        }
+        let col: usize = prefix
+            .utf8_chunks()
+            .map(|chunk| chunk.valid().chars().count() + chunk.invalid().len())
+            .sum();
 
         (line as u32, col as u32)
     }
@@ -109,6 +129,15 @@ impl SourceSpan {
         SourceSpan::new(SourceFile::unknown(), 0, 0)
     }
 
+    pub fn is_unknown(&self) -> bool {
+        self.file == SourceFile::unknown()
+    }
+
+    /// The span from the start of `self` to the end of `end`.
+    pub fn to(&self, end: SourceSpan) -> SourceSpan {
+        SourceSpan::new(self.file, self.begin(), end.end().max(self.begin()))
+    }
+
     pub fn file(&self) -> SourceFile {
         self.file
     }
@@ -154,12 +183,41 @@ impl SourceSpanInfo {
 
 pub trait Errors {
     fn push(&self, int_loc: &InternalLocation, loc: &dyn Locatable, message: String, context: Vec<String>);
+
+    /// Sinks that don't keep warnings apart report them like errors.
+    fn push_warning(&self, int_loc: &InternalLocation, loc: &dyn Locatable, message: String, context: Vec<String>) {
+        self.push(int_loc, loc, message, context);
+    }
+}
+
+/// Passes that report everything, errors and warnings alike, to another sink.
+pub trait ForwardErrors {
+    fn errors(&self) -> &dyn Errors;
 }
 
-pub fn push_error(errors: &impl Errors, int_loc: &InternalLocation, loc: &impl Locatable, message: String) {
+impl<T: ForwardErrors> Errors for T {
+    fn push(&self, int_loc: &InternalLocation, loc: &dyn Locatable, message: String, context: Vec<String>) {
+        self.errors().push(int_loc, loc, message, context);
+    }
+    fn push_warning(&self, int_loc: &InternalLocation, loc: &dyn Locatable, message: String, context: Vec<String>) {
+        self.errors().push_warning(int_loc, loc, message, context);
+    }
+}
+
+pub fn push_error(errors: &(impl Errors + ?Sized), int_loc: &InternalLocation, loc: &impl Locatable, message: String) {
     errors.push(int_loc, loc, message, Vec::new());
 }
 
+pub fn push_warning(errors: &(impl Errors + ?Sized), int_loc: &InternalLocation, loc: &impl Locatable, message: String) {
+    errors.push_warning(int_loc, loc, message, Vec::new());
+}
+
+pub struct NullErrors;
+
+impl Errors for NullErrors {
+    fn push(&self, _: &InternalLocation, _: &dyn Locatable, _: String, _: Vec<String>) {}
+}
+
 #[derive(Clone)]
 pub struct InternalLocation {
     pub file: &'static str,
@@ -174,7 +232,10 @@ pub struct ErrorInfo {
     pub context: Vec<String>,
 }
 
-pub trait Locator {}
+/// Resolves locations that are not stored inline, such as AST node ids.
+pub trait Locator {
+    fn node_span(&self, node: NodeId) -> SourceSpan;
+}
 
 pub trait Locatable {
     fn source_span(&self, locator: &dyn Locator) -> SourceSpan;
@@ -183,9 +244,13 @@ pub trait Locatable {
 #[derive(Default)]
 pub struct Compiler {
     errors: Mutex<Vec<ErrorInfo>>,
+    warnings: Mutex<Vec<ErrorInfo>>,
     files_by_name: DashMap<String, Arc<SourceFileInfo>>,
     files_by_file: DashMap<SourceFile, Arc<SourceFileInfo>>,
     file_index: AtomicUsize,
+    // Keyed by the first id of each table.
+    node_tables: RwLock<BTreeMap<usize, Arc<NodeTable>>>,
+    node_index: AtomicUsize,
 }
 
 impl Compiler {
@@ -226,7 +291,7 @@ impl Compiler {
             name: path.to_string(),
             data: source,
             file,
-            line_breaks: OnceCell::new(),
+            line_breaks: OnceLock::new(),
         };
 
         let arc = Arc::new(info);
@@ -234,6 +299,26 @@ impl Compiler {
         arc
     }
 
+    /// Numbers the nodes of `root` so diagnostics can be reported against their ids
+    /// until the returned map is dropped.
+    pub fn add_nodes<'a>(&'a self, root: &'a AstRoot) -> NodeMap<'a> {
+        let mut map = NodeMap::new(root);
+        let first = self.node_index.fetch_add(map.len(), Ordering::Relaxed);
+        map.register(self, first);
+        self.node_tables.write().insert(first, map.table().clone());
+        map
+    }
+
+    pub(crate) fn remove_nodes(&self, root: NodeId) {
+        self.node_tables.write().remove(&root.index());
+    }
+
+    pub fn node_table(&self, node: NodeId) -> Option<Arc<NodeTable>> {
+        let tables = self.node_tables.read();
+        let (_, table) = tables.range(..=node.index()).next_back()?;
+        table.contains(node).then(|| table.clone())
+    }
+
     pub fn file_info(&self, file: SourceFile) -> Option<Arc<SourceFileInfo>> {
         self.files_by_file.get(&file).map(|c| c.clone())
     }
@@ -256,9 +341,17 @@ impl Compiler {
     pub fn errors(&self) -> Vec<ErrorInfo> {
         self.errors.lock().clone()
     }
+
+    pub fn warnings(&self) -> Vec<ErrorInfo> {
+        self.warnings.lock().clone()
+    }
 }
 
-impl Locator for Compiler {}
+impl Locator for Compiler {
+    fn node_span(&self, node: NodeId) -> SourceSpan {
+        self.node_table(node).and_then(|table| table.span(node)).unwrap_or_else(SourceSpan::unknown)
+    }
+}
 
 impl Errors for Compiler {
     fn push(&self, int_loc: &InternalLocation, loc: &dyn Locatable, message: String, context: Vec<String>) {
@@ -274,4 +367,18 @@ impl Errors for Compiler {
         let mut errors = self.errors.lock();
         errors.push(info);
     }
+
+    fn push_warning(&self, int_loc: &InternalLocation, loc: &dyn Locatable, message: String, context: Vec<String>) {
+        let location = loc.source_span(self);
+
+        let info = ErrorInfo {
+            message,
+            location,
+            internal_location: int_loc.clone(),
+            context,
+        };
+
+        let mut warnings = self.warnings.lock();
+        warnings.push(info);
+    }
 }
@@ -0,0 +1,456 @@
+use std::borrow::Cow;
+
+use crate::{ast::*, front::token_spelling};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Indent {
+    Spaces(usize),
+    Tabs,
+}
+
+impl Default for Indent {
+    fn default() -> Self {
+        Indent::Spaces(4)
+    }
+}
+
+impl Indent {
+    fn unit(self) -> String {
+        match self {
+            Indent::Spaces(n) => " ".repeat(n),
+            Indent::Tabs => "\t".to_string(),
+        }
+    }
+}
+
+/// Renders `root` back into ebi source in canonical layout. Token text is taken
+/// from `source`, so literals and identifiers are spelled as they were written.
+/// Ordinary comments are not part of the tree and are dropped.
+pub fn dump(source: &[u8], root: &AstRoot, indent: Indent) -> String {
+    let mut printer = Printer {
+        source,
+        unit: indent.unit(),
+        depth: 0,
+        out: String::new(),
+    };
+    printer.root(root.root());
+    printer.out
+}
+
+struct Printer<'s> {
+    source: &'s [u8],
+    unit: String,
+    depth: usize,
+    out: String,
+}
+
+// Functions and classes are set apart from their neighbours by a blank line.
+fn spaced(ast: &Ast) -> bool {
+    matches!(ast, Ast::ClassDecl(..) | Ast::FnDecl(..))
+}
+
+impl<'s> Printer<'s> {
+    fn text(&self, token: &Token) -> Cow<'s, str> {
+        if token.span.is_unknown() {
+            // Tokens synthesized by the parser only have their kind.
+            let spelling = token_spelling(token.kind).unwrap_or_default();
+            return String::from_utf8_lossy(spelling);
+        }
+        let text = self.source.get(token.span.begin()..token.span.end()).unwrap_or_default();
+        String::from_utf8_lossy(text)
+    }
+
+    fn write(&mut self, text: &str) {
+        self.out.push_str(text);
+    }
+
+    fn token(&mut self, token: &Token) {
+        let text = self.text(token);
+        self.out.push_str(&text);
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.depth {
+            self.out.push_str(&self.unit);
+        }
+    }
+
+    fn list<'a>(&mut self, items: &'a [Ast<'a>], mut f: impl FnMut(&mut Self, &'a Ast<'a>)) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            f(self, item);
+        }
+    }
+
+    // Lays out declarations or statements one per line, starting on the current line.
+    fn lines<'a>(&mut self, items: &'a [Ast<'a>]) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                if spaced(&items[i - 1]) || spaced(item) {
+                    self.out.push('\n');
+                }
+                self.newline();
+            }
+            self.node(item);
+        }
+    }
+
+    fn docs(&mut self, docs: &[Token], kind: TokenKind) {
+        for doc in docs.iter().filter(|doc| doc.kind == kind) {
+            let text = self.text(doc);
+            self.write(text.trim_end());
+            self.newline();
+        }
+    }
+
+    fn root<'a>(&mut self, ast: &'a Ast<'a>) {
+        let Ast::Root(docs, decls) = ast else {
+            self.node(ast);
+            self.out.push('\n');
+            return
+        };
+        self.docs(docs, TokenKind::InnerDocComment);
+        if !docs.is_empty() && !decls.is_empty() {
+            self.out.push('\n');
+        }
+        self.lines(decls);
+        if !decls.is_empty() {
+            self.out.push('\n');
+        }
+    }
+
+    fn vis(&mut self, vis: &Option<Token>) {
+        if let Some(vis) = vis {
+            self.token(vis);
+            self.write(" ");
+        }
+    }
+
+    fn block<'a>(&mut self, open: &str, docs: &[Token], items: &'a [Ast<'a>]) {
+        self.write(open);
+        let inner = docs.iter().any(|doc| doc.kind == TokenKind::InnerDocComment);
+        if items.is_empty() && !inner {
+            self.write("}");
+            return
+        }
+        self.depth += 1;
+        self.newline();
+        self.docs(docs, TokenKind::InnerDocComment);
+        if items.is_empty() {
+            // Drop the indentation written after the last doc comment.
+            self.out.truncate(self.out.trim_end_matches([' ', '\t', '\n']).len());
+        }
+        self.lines(items);
+        self.depth -= 1;
+        self.newline();
+        self.write("}");
+    }
+
+    fn node<'a>(&mut self, ast: &'a Ast<'a>) {
+        match ast {
+            Ast::Error(token) => {
+                if !token.span.is_unknown() {
+                    self.token(token);
+                }
+            }
+            Ast::Root(..) => self.root(ast),
+            Ast::Import(kw, path, source) => {
+                self.token(kw);
+                self.write(" ");
+                for (i, name) in path.iter().enumerate() {
+                    if i > 0 {
+                        self.write(".");
+                    }
+                    self.token(name);
+                }
+                if let Some(source) = source {
+                    self.write(" from ");
+                    self.token(source);
+                }
+            }
+            Ast::ClassDecl(docs, vis, kw, name, generics, base, members) => {
+                self.docs(docs, TokenKind::DocComment);
+                self.vis(vis);
+                self.token(kw);
+                self.write(" ");
+                self.token(name);
+                if let Some(generics) = generics {
+                    self.node(generics);
+                }
+                if let Some(base) = base {
+                    self.write(": ");
+                    self.ty(base);
+                }
+                self.block(" {", docs, members);
+            }
+            Ast::Field(docs, vis, name, ty, init) => {
+                self.docs(docs, TokenKind::DocComment);
+                self.vis(vis);
+                self.token(name);
+                self.write(": ");
+                self.ty(ty);
+                if let Some(init) = init {
+                    self.write(" = ");
+                    self.node(init);
+                }
+            }
+            Ast::FnDecl(docs, vis, kw, name, generics, signature, body) => {
+                self.docs(docs, TokenKind::DocComment);
+                self.vis(vis);
+                self.token(kw);
+                self.write(" ");
+                self.token(name);
+                if let Some(generics) = generics {
+                    self.node(generics);
+                }
+                self.node(signature);
+                self.write(" ");
+                self.node(body);
+            }
+            Ast::Generics(_, params, _) => {
+                self.write("<");
+                self.list(params, Self::node);
+                self.write(">");
+            }
+            Ast::GenericParam(name, bounds) => {
+                self.token(name);
+                for (i, bound) in bounds.iter().enumerate() {
+                    self.write(if i == 0 { ": " } else { " + " });
+                    self.ty(bound);
+                }
+            }
+            Ast::Signature(_, params, _, ret) => {
+                self.write("(");
+                self.list(params, Self::node);
+                self.write(")");
+                self.ret(ret);
+            }
+            Ast::Param(name, ty) => {
+                self.token(name);
+                self.write(": ");
+                self.ty(ty);
+            }
+            Ast::Closure(kw, signature, body) => match (kw, signature) {
+                (Some(kw), _) => {
+                    self.token(kw);
+                    self.node(signature);
+                    self.write(" ");
+                    self.node(body);
+                }
+                (None, Ast::Signature(open, params, _, ret)) => {
+                    if open.kind == TokenKind::LogicalOr {
+                        self.write("||");
+                    } else {
+                        self.write("|");
+                        self.list(params, Self::node);
+                        self.write("|");
+                    }
+                    self.ret(ret);
+                    self.write(" ");
+                    self.node(body);
+                }
+                (None, _) => self.node(body),
+            },
+            Ast::Block(_, items, _) => self.block("{", &[], items),
+            Ast::Let(kw, name, ty, init) => {
+                self.token(kw);
+                self.write(" ");
+                self.token(name);
+                if let Some(ty) = ty {
+                    self.write(": ");
+                    self.ty(ty);
+                }
+                if let Some(init) = init {
+                    self.write(" = ");
+                    self.node(init);
+                }
+            }
+            Ast::If(kw, cond, then, otherwise) => {
+                self.token(kw);
+                self.write(" ");
+                self.node(cond);
+                self.write(" ");
+                self.node(then);
+                if let Some(otherwise) = otherwise {
+                    self.write(" else ");
+                    self.node(otherwise);
+                }
+            }
+            Ast::While(kw, cond, body) => {
+                self.token(kw);
+                self.write(" ");
+                self.node(cond);
+                self.write(" ");
+                self.node(body);
+            }
+            Ast::For(kw, name, iter, body) => {
+                self.token(kw);
+                self.write(" ");
+                self.token(name);
+                self.write(" in ");
+                self.node(iter);
+                self.write(" ");
+                self.node(body);
+            }
+            Ast::Return(kw, value) => {
+                self.token(kw);
+                if let Some(value) = value {
+                    self.write(" ");
+                    self.node(value);
+                }
+            }
+            Ast::Break(kw) | Ast::Continue(kw) => self.token(kw),
+            Ast::Match(kw, scrutinee, _, arms, _) => {
+                self.token(kw);
+                self.write(" ");
+                self.node(scrutinee);
+                self.block(" {", &[], arms);
+            }
+            Ast::Arm(pattern, guard, arrow, body) => {
+                self.node(pattern);
+                if let Some(guard) = guard {
+                    self.write(" if ");
+                    self.node(guard);
+                }
+                self.write(" ");
+                self.token(arrow);
+                self.write(" ");
+                self.node(body);
+            }
+            Ast::Wildcard(token) | Ast::Bind(token) | Ast::Rest(token) | Ast::Name(token) | Ast::Literal(token) => {
+                self.token(token)
+            }
+            Ast::StructPattern(base, _, fields, _) => {
+                self.node(base);
+                if fields.is_empty() {
+                    self.write(" {}");
+                } else {
+                    self.write(" { ");
+                    self.list(fields, Self::node);
+                    self.write(" }");
+                }
+            }
+            Ast::FieldPattern(name, pattern) => {
+                self.token(name);
+                if let Some(pattern) = pattern {
+                    self.write(": ");
+                    self.node(pattern);
+                }
+            }
+            Ast::TuplePattern(_, items, _) => self.tuple(items, Self::node),
+            Ast::OrPattern(alternatives) => {
+                for (i, alternative) in alternatives.iter().enumerate() {
+                    if i > 0 {
+                        self.write(" | ");
+                    }
+                    self.node(alternative);
+                }
+            }
+            Ast::RangePattern(lo, op, hi) => {
+                self.node(lo);
+                self.token(op);
+                self.node(hi);
+            }
+            Ast::Path(base, sep, name) => {
+                self.node(base);
+                self.token(sep);
+                self.token(name);
+            }
+            // Generic arguments in an expression need a turbofish.
+            Ast::Generic(base, _, args, _) => {
+                self.node(base);
+                self.write("::<");
+                self.list(args, Self::ty);
+                self.write(">");
+            }
+            Ast::ArrayType(..) | Ast::OptionalType(..) | Ast::TupleType(..) | Ast::FnType(..) => self.ty(ast),
+            Ast::Paren(_, expr, _) => {
+                self.write("(");
+                self.node(expr);
+                self.write(")");
+            }
+            Ast::Call(callee, _, args, _) => {
+                self.node(callee);
+                self.write("(");
+                self.list(args, Self::node);
+                self.write(")");
+            }
+            Ast::Member(base, dot, name) => {
+                self.node(base);
+                self.token(dot);
+                self.token(name);
+            }
+            Ast::Index(base, _, index, _) => {
+                self.node(base);
+                self.write("[");
+                self.node(index);
+                self.write("]");
+            }
+            Ast::Unop(op, expr) => {
+                self.token(op);
+                self.node(expr);
+            }
+            Ast::Binop(op, lhs, rhs) => {
+                self.node(lhs);
+                self.write(" ");
+                self.token(op);
+                self.write(" ");
+                self.node(rhs);
+            }
+        }
+    }
+
+    fn ret<'a>(&mut self, ret: &Option<&'a Ast<'a>>) {
+        if let Some(ret) = ret {
+            self.write(" -> ");
+            self.ty(ret);
+        }
+    }
+
+    // A one-element tuple keeps its trailing comma to tell it apart from parentheses.
+    fn tuple<'a>(&mut self, items: &'a [Ast<'a>], f: impl FnMut(&mut Self, &'a Ast<'a>)) {
+        self.write("(");
+        self.list(items, f);
+        if items.len() == 1 {
+            self.write(",");
+        }
+        self.write(")");
+    }
+
+    fn ty<'a>(&mut self, ast: &'a Ast<'a>) {
+        match ast {
+            Ast::Generic(base, _, args, _) => {
+                self.ty(base);
+                self.write("<");
+                self.list(args, Self::ty);
+                self.write(">");
+            }
+            Ast::ArrayType(_, elem, _) => {
+                self.write("[");
+                self.ty(elem);
+                self.write("]");
+            }
+            Ast::OptionalType(ty, question) => {
+                self.ty(ty);
+                self.token(question);
+            }
+            Ast::TupleType(_, types, _) => self.tuple(types, Self::ty),
+            Ast::FnType(kw, _, params, _, ret) => {
+                self.token(kw);
+                self.write("(");
+                self.list(params, Self::ty);
+                self.write(")");
+                self.ret(ret);
+            }
+            Ast::Paren(_, ty, _) => {
+                self.write("(");
+                self.ty(ty);
+                self.write(")");
+            }
+            _ => self.node(ast),
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use crate::{
+    ast::*,
+    front::{ident_name, string_value},
+    *,
+};
+
+struct Lowerer<'s> {
+    source: &'s [u8],
+}
+
+impl<'s> Lowerer<'s> {
+    fn text(&self, token: &Token) -> &'s [u8] {
+        self.source.get(token.span.begin()..token.span.end()).unwrap_or_default()
+    }
+
+    fn ident(&self, token: &Token) -> Ident {
+        Ident {
+            name: ident_name(self.text(token)).into_owned(),
+            span: token.span,
+        }
+    }
+
+    fn docs(&self, docs: &[Token]) -> Vec<String> {
+        docs.iter()
+            .map(|doc| {
+                let text = String::from_utf8_lossy(self.text(doc));
+                let text = text.get(3..).unwrap_or_default();
+                text.strip_prefix(' ').unwrap_or(text).trim_end().to_string()
+            })
+            .collect()
+    }
+
+    fn vis(vis: &Option<Token>) -> Visibility {
+        match vis {
+            Some(_) => Visibility::Public,
+            None => Visibility::Private,
+        }
+    }
+
+    fn literal(&self, token: &Token) -> Option<Literal> {
+        let literal = match (token.kind, token.value) {
+            (TokenKind::Integer, TokenValue::Integer(value)) => Literal::Integer(value),
+            (TokenKind::Float, TokenValue::Float(value)) => Literal::Float(value),
+            (TokenKind::Char, TokenValue::Char(value)) => Literal::Char(value),
+            (TokenKind::String, _) => Literal::String(string_value(self.text(token))),
+            (TokenKind::KeywordTrue, _) => Literal::Bool(true),
+            (TokenKind::KeywordFalse, _) => Literal::Bool(false),
+            (TokenKind::KeywordNull, _) => Literal::Null,
+            // Malformed literals were already reported by the tokenizer.
+            _ => return None,
+        };
+        Some(literal)
+    }
+
+    fn root(&self, ast: &Ast) -> Root {
+        let Ast::Root(docs, decls) = ast else {
+            return Root { docs: Vec::new(), decls: vec![Decl::error(ast.span())] }
+        };
+        Root {
+            docs: self.docs(docs),
+            decls: decls.iter().map(|d| self.decl(d)).collect(),
+        }
+    }
+
+    fn decl(&self, ast: &Ast) -> Decl {
+        let kind = match ast {
+            Ast::Import(_, path, source) => DeclKind::Import(ImportDecl {
+                path: path.iter().map(|t| self.ident(t)).collect(),
+                source: source.as_ref().map(|s| string_value(self.text(s))),
+            }),
+            Ast::ClassDecl(docs, vis, kw, name, generics, base, decls) => DeclKind::Class(ClassDecl {
+                docs: self.docs(docs),
+                vis: Lowerer::vis(vis),
+                kind: match kw.kind {
+                    TokenKind::KeywordStruct => ClassKind::Struct,
+                    _ => ClassKind::Class,
+                },
+                name: self.ident(name),
+                generics: self.generics(*generics),
+                base: base.map(|b| self.ty(b)),
+                members: decls.iter().map(|d| self.decl(d)).collect(),
+            }),
+            Ast::Field(docs, vis, name, ty, init) => DeclKind::Field(FieldDecl {
+                docs: self.docs(docs),
+                vis: Lowerer::vis(vis),
+                name: self.ident(name),
+                ty: self.ty(ty),
+                init: init.map(|i| self.expr(i)),
+            }),
+            Ast::FnDecl(docs, vis, _, name, generics, sig, body) => {
+                let (params, ret) = self.signature(sig);
+                DeclKind::Fn(FnDecl {
+                    docs: self.docs(docs),
+                    vis: Lowerer::vis(vis),
+                    name: self.ident(name),
+                    generics: self.generics(*generics),
+                    params,
+                    ret,
+                    body: self.block(body),
+                })
+            }
+            Ast::Error(_) => DeclKind::Error,
+            expr => DeclKind::Expr(self.expr(expr)),
+        };
+        Decl::new(kind, ast.span())
+    }
+
+    fn generics(&self, generics: Option<&Ast>) -> Vec<GenericParam> {
+        let Some(Ast::Generics(_, params, _)) = generics else { return Vec::new() };
+        params
+            .iter()
+            .filter_map(|param| match param {
+                Ast::GenericParam(name, bounds) => Some(GenericParam {
+                    name: self.ident(name),
+                    bounds: bounds.iter().map(|b| self.ty(b)).collect(),
+                    span: param.span(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn signature(&self, ast: &Ast) -> (Vec<Param>, Option<Type>) {
+        let Ast::Signature(_, params, _, ret) = ast else { return (Vec::new(), None) };
+        let params = params
+            .iter()
+            .filter_map(|param| match param {
+                Ast::Param(name, ty) => Some(Param {
+                    name: self.ident(name),
+                    ty: Some(self.ty(ty)),
+                    span: param.span(),
+                }),
+                // Closure parameters may leave out their type.
+                Ast::Bind(name) => Some(Param {
+                    name: self.ident(name),
+                    ty: None,
+                    span: param.span(),
+                }),
+                _ => None,
+            })
+            .collect();
+        (params, ret.map(|r| self.ty(r)))
+    }
+
+    fn ty(&self, ast: &Ast) -> Type {
+        let kind = match ast {
+            Ast::Name(name) => TypeKind::Named(self.ident(name)),
+            Ast::Path(base, _, name) => TypeKind::Path(self.ty(base), self.ident(name)),
+            Ast::Generic(base, _, args, _) => TypeKind::Generic(self.ty(base), args.iter().map(|a| self.ty(a)).collect()),
+            Ast::ArrayType(_, elem, _) => TypeKind::Array(self.ty(elem)),
+            Ast::OptionalType(ty, _) => TypeKind::Optional(self.ty(ty)),
+            Ast::TupleType(_, types, _) => TypeKind::Tuple(types.iter().map(|t| self.ty(t)).collect()),
+            Ast::FnType(_, _, params, _, ret) => {
+                TypeKind::Fn(params.iter().map(|p| self.ty(p)).collect(), ret.map(|r| self.ty(r)))
+            }
+            Ast::Paren(_, inner, _) => *self.ty(inner).kind,
+            _ => TypeKind::Error,
+        };
+        Type::new(kind, ast.span())
+    }
+
+    fn block(&self, ast: &Ast) -> Block {
+        let stmts = match ast {
+            Ast::Block(_, items, _) => items.iter().map(|i| self.stmt(i)).collect(),
+            _ => vec![Stmt::error(ast.span())],
+        };
+        Block { stmts, span: ast.span() }
+    }
+
+    fn stmt(&self, ast: &Ast) -> Stmt {
+        let kind = match ast {
+            Ast::Let(kw, name, ty, init) => {
+                let mutability = match kw.kind {
+                    TokenKind::KeywordVar => Mutability::Mutable,
+                    _ => Mutability::Immutable,
+                };
+                StmtKind::Let(mutability, self.ident(name), ty.map(|t| self.ty(t)), init.map(|i| self.expr(i)))
+            }
+            Ast::If(_, cond, then, otherwise) => {
+                StmtKind::If(self.expr(cond), self.block(then), otherwise.map(|o| self.stmt(o)))
+            }
+            Ast::While(_, cond, body) => StmtKind::While(self.expr(cond), self.block(body)),
+            Ast::For(_, name, iter, body) => StmtKind::For(self.ident(name), self.expr(iter), self.block(body)),
+            Ast::Return(_, value) => StmtKind::Return(value.map(|v| self.expr(v))),
+            Ast::Break(_) => StmtKind::Break,
+            Ast::Continue(_) => StmtKind::Continue,
+            Ast::Block(..) => StmtKind::Block(self.block(ast)),
+            Ast::Error(_) => StmtKind::Error,
+            expr => StmtKind::Expr(self.expr(expr)),
+        };
+        Stmt::new(kind, ast.span())
+    }
+
+    fn expr(&self, ast: &Ast) -> Expr {
+        let kind = match ast {
+            Ast::Name(name) => ExprKind::Identifier(self.ident(name)),
+            Ast::Literal(token) => match self.literal(token) {
+                Some(literal) => ExprKind::Literal(literal),
+                None => ExprKind::Error,
+            },
+            // Parentheses only group, the inner expression takes over their span.
+            Ast::Paren(_, inner, _) => *self.expr(inner).kind,
+            Ast::Unop(op, operand) => match UnaryOp::from_token(op.kind) {
+                Some(op) => ExprKind::UnaryOp(op, self.expr(operand)),
+                None => ExprKind::Error,
+            },
+            Ast::Binop(op, lhs, rhs) => match BinaryOp::from_token(op.kind) {
+                Some(op) => ExprKind::BinaryOp(op, self.expr(lhs), self.expr(rhs)),
+                None => ExprKind::Error,
+            },
+            Ast::Call(callee, _, args, _) => ExprKind::Call(self.expr(callee), args.iter().map(|a| self.expr(a)).collect()),
+            Ast::Member(base, _, name) => ExprKind::Member(self.expr(base), self.ident(name)),
+            Ast::Index(base, _, index, _) => ExprKind::Index(self.expr(base), self.expr(index)),
+            Ast::Path(base, _, name) => ExprKind::Path(self.expr(base), self.ident(name)),
+            Ast::Generic(base, _, args, _) => ExprKind::Generic(self.expr(base), args.iter().map(|a| self.ty(a)).collect()),
+            Ast::Match(_, scrutinee, _, arms, _) => ExprKind::Match(self.expr(scrutinee), arms.iter().filter_map(|a| self.arm(a)).collect()),
+            Ast::Closure(_, sig, body) => {
+                let (params, ret) = self.signature(sig);
+                ExprKind::Closure(Closure { params, ret, body: self.expr(body) })
+            }
+            Ast::Block(..) => ExprKind::Block(self.block(ast)),
+            _ => ExprKind::Error,
+        };
+        Expr::new(kind, ast.span())
+    }
+
+    fn arm(&self, ast: &Ast) -> Option<Arm> {
+        let Ast::Arm(pattern, guard, _, body) = ast else { return None };
+        Some(Arm {
+            pattern: self.pattern(pattern),
+            guard: guard.map(|g| self.expr(g)),
+            body: self.expr(body),
+            span: ast.span(),
+        })
+    }
+
+    fn path(&self, ast: &Ast, segments: &mut Vec<Ident>) {
+        match ast {
+            Ast::Name(name) | Ast::Bind(name) => segments.push(self.ident(name)),
+            Ast::Path(base, _, name) => {
+                self.path(base, segments);
+                segments.push(self.ident(name));
+            }
+            _ => {}
+        }
+    }
+
+    fn pattern(&self, ast: &Ast) -> Pattern {
+        let kind = match ast {
+            Ast::Wildcard(_) => PatternKind::Wildcard,
+            Ast::Bind(name) => PatternKind::Bind(self.ident(name)),
+            Ast::Literal(_) | Ast::Unop(..) => PatternKind::Literal(self.expr(ast)),
+            Ast::RangePattern(lo, op, hi) => {
+                let end = match op.kind {
+                    TokenKind::DotDotEq => RangeEnd::Inclusive,
+                    _ => RangeEnd::Exclusive,
+                };
+                PatternKind::Range(self.expr(lo), end, self.expr(hi))
+            }
+            Ast::Path(..) => {
+                let mut segments = Vec::new();
+                self.path(ast, &mut segments);
+                PatternKind::Path(segments)
+            }
+            Ast::StructPattern(base, _, fields, _) => {
+                let mut segments = Vec::new();
+                self.path(base, &mut segments);
+                let rest = fields.iter().any(|f| matches!(f, Ast::Rest(_)));
+                let fields = fields
+                    .iter()
+                    .filter_map(|field| {
+                        let Ast::FieldPattern(name, pattern) = field else { return None };
+                        // `Point { x }` is shorthand for `Point { x: x }`.
+                        let pattern = match pattern {
+                            Some(pattern) => self.pattern(pattern),
+                            None => Pattern::new(PatternKind::Bind(self.ident(name)), name.span),
+                        };
+                        Some(FieldPattern { name: self.ident(name), pattern })
+                    })
+                    .collect();
+                PatternKind::Struct(segments, fields, rest)
+            }
+            Ast::TuplePattern(_, items, _) => PatternKind::Tuple(items.iter().map(|i| self.pattern(i)).collect()),
+            Ast::OrPattern(alts) => PatternKind::Or(alts.iter().map(|a| self.pattern(a)).collect()),
+            Ast::Paren(_, inner, _) => *self.pattern(inner).kind,
+            _ => PatternKind::Error,
+        };
+        Pattern::new(kind, ast.span())
+    }
+}
+
+/// Lowers a parsed file into the owned, typed tree.
+pub fn lower(source: &[u8], root: &AstRoot) -> Root {
+    let lowerer = Lowerer { source };
+    lowerer.root(root.root())
+}
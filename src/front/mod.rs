@@ -1,4 +1,11 @@
+mod lower;
 mod parser;
 mod tokenizer;
+pub use lower::lower;
 pub use parser::parse;
-pub use tokenizer::tokenize;
+pub use tokenizer::{
+    contextual_keyword_spelling, ident_name, relex, split_trivia, string_value, token_spelling, tokenize,
+    tokenize_lossless, TextEdit,
+};
+#[cfg(feature = "bench")]
+pub use tokenizer::tokenize_scalar;
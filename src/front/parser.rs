@@ -1,13 +1,62 @@
+use std::cell::Cell;
+
 use bumpalo::Bump;
 
 use crate::{ast::*, *};
 
+// Binds tighter than every binary operator.
+const UNARY_PRECEDENCE: u8 = 11;
+
+// Recovery skips ahead to one of these tokens, ignoring any inside nested braces.
+const DECL_SYNC: &[TokenKind] = &[
+    TokenKind::Newline,
+    TokenKind::KeywordFn,
+    TokenKind::KeywordClass,
+    TokenKind::KeywordStruct,
+    TokenKind::KeywordImport,
+    TokenKind::KeywordPub,
+    TokenKind::DocComment,
+];
+const MEMBER_SYNC: &[TokenKind] = &[
+    TokenKind::Newline,
+    TokenKind::Semicolon,
+    TokenKind::BraceClose,
+    TokenKind::KeywordFn,
+    TokenKind::KeywordClass,
+    TokenKind::KeywordStruct,
+    TokenKind::KeywordPub,
+    TokenKind::DocComment,
+];
+const STMT_SYNC: &[TokenKind] = &[TokenKind::Newline, TokenKind::Semicolon, TokenKind::BraceClose];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Expected {
+    Token(TokenKind),
+    Expression,
+}
+
+impl std::fmt::Display for Expected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expected::Token(kind) => write!(f, "{}", kind),
+            Expected::Expression => write!(f, "an expression"),
+        }
+    }
+}
+
 struct Parser<'a, 'b: 'a> {
     tokens: &'a mut dyn Iterator<Item = Token>,
     token: Token,
     errors: &'a dyn Errors,
     bump: &'b Bump,
     temp_lists: Vec<Vec<Ast<'b>>>,
+    nesting: u32,
+    reported: Cell<bool>,
+    // What the parser tried to match at the current token, for "expected one of" messages.
+    expected: Vec<Expected>,
+    last_end: usize,
+    // Set when the current token follows a newline that was skipped inside parentheses.
+    skipped_newline: bool,
 }
 
 impl<'a, 'b> Parser<'a, 'b> {
@@ -18,6 +67,11 @@ impl<'a, 'b> Parser<'a, 'b> {
             errors,
             bump,
             temp_lists: Vec::new(),
+            nesting: 0,
+            reported: Cell::new(false),
+            expected: Vec::new(),
+            last_end: 0,
+            skipped_newline: false,
         }
     }
 
@@ -30,10 +84,7 @@ impl<'a, 'b> Parser<'a, 'b> {
     }
 
     fn begin_list(&mut self) -> Vec<Ast<'b>> {
-        match self.temp_lists.pop() {
-            Some(list) => list,
-            None => Vec::new(),
-        }
+        self.temp_lists.pop().unwrap_or_default()
     }
 
     fn push_list(&mut self, list: Vec<Ast<'b>>) -> &'b [Ast<'b>] {
@@ -45,141 +96,1319 @@ impl<'a, 'b> Parser<'a, 'b> {
     }
 
     fn advance(&mut self) -> Token {
-        let token = match self.tokens.next() {
-            Some(token) => token,
-            None => Token::end(),
+        self.skipped_newline = false;
+        let token = loop {
+            match self.tokens.next() {
+                // Newlines are insignificant inside parentheses.
+                Some(token) if token.kind == TokenKind::Newline && self.nesting > 0 => self.skipped_newline = true,
+                Some(token) => break token,
+                None => break Token::end(),
+            }
         };
-        std::mem::replace(&mut self.token, token)
+        let prev = std::mem::replace(&mut self.token, token);
+        if !matches!(prev.kind, TokenKind::Newline | TokenKind::End) {
+            self.last_end = prev.span.end();
+        }
+        self.expected.clear();
+        prev
+    }
+
+    fn expect(&mut self, expected: Expected) {
+        if !self.expected.contains(&expected) {
+            self.expected.push(expected);
+        }
+    }
+
+    fn expect_tokens(&mut self, kinds: &[TokenKind]) {
+        kinds.iter().for_each(|&kind| self.expect(Expected::Token(kind)));
     }
 
     fn accept(&mut self, tk: TokenKind) -> Option<Token> {
         if self.token.kind == tk {
             Some(self.advance())
         } else {
+            self.expect(Expected::Token(tk));
             None
         }
     }
 
+    // Reports the current token against everything the parser tried to match here.
+    fn unexpected(&mut self) {
+        let found = self.token.kind;
+        match self.expected.as_slice() {
+            [] => error!(self, &self.token, "unexpected {}", found),
+            [expected] => error!(self, &self.token, "expected {} but found {}", expected, found),
+            expected => {
+                let expected = expected.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                error!(self, &self.token, "expected one of {} but found {}", expected.join(", "), found)
+            }
+        }
+    }
+
     fn skip_newlines(&mut self) {
-        while self.accept(TokenKind::Newline).is_some() {}
+        while self.token.kind == TokenKind::Newline {
+            self.advance();
+        }
+    }
+
+    fn parse_docs(&mut self, docs: &mut Vec<Token>, kind: TokenKind) {
+        while self.token.kind == kind {
+            docs.push(self.advance());
+            self.skip_newlines();
+        }
+    }
+
+    fn finish_paren(&mut self, open: Token) -> Ast<'b> {
+        self.nesting += 1;
+        self.skip_newlines();
+        let expr = self.parse_operand(&open, 0);
+        self.nesting -= 1;
+
+        let Some(close) = self.accept(TokenKind::ParenClose) else {
+            error!(self, &self.token, "expected {} to close {}, found {}", TokenKind::ParenClose, TokenKind::ParenOpen, self.token.kind);
+            return Ast::error(open)
+        };
+        Ast::Paren(open, self.push(expr), close)
     }
 
     fn parse_atom(&mut self) -> Option<Ast<'b>> {
         match self.token.kind {
             TokenKind::Ident => Some(Ast::Name(self.advance())),
+            TokenKind::Integer
+            | TokenKind::Float
+            | TokenKind::String
+            | TokenKind::Char
+            | TokenKind::KeywordTrue
+            | TokenKind::KeywordFalse
+            | TokenKind::KeywordNull => Some(Ast::Literal(self.advance())),
+            TokenKind::ParenOpen => {
+                let open = self.advance();
+                Some(self.finish_paren(open))
+            }
+            TokenKind::KeywordMatch => {
+                let kw = self.advance();
+                Some(self.finish_match(kw))
+            }
+            TokenKind::BitOr | TokenKind::LogicalOr => Some(self.parse_closure()),
+            TokenKind::KeywordFn => {
+                let kw = self.advance();
+                Some(self.finish_fn_closure(kw))
+            }
             _ => None,
         }
     }
 
-    fn parse_term(&mut self) -> Option<Ast<'b>> {
-        let mut lhs = self.parse_atom()?;
+    // Closure parameters may leave out their types, unlike function parameters.
+    fn parse_closure_params(&mut self, close: TokenKind) -> &'b [Ast<'b>] {
+        let mut params = self.begin_list();
+        while self.token.kind != close {
+            let Some(name) = self.accept(TokenKind::Ident) else {
+                error!(self, &self.token, "expected a closure parameter, found {}", self.token.kind);
+                break;
+            };
+            let param = match self.accept(TokenKind::Colon) {
+                Some(colon) => {
+                    let ty = self.parse_type_operand(&colon);
+                    Ast::Param(name, self.push(ty))
+                }
+                None => Ast::Bind(name),
+            };
+            params.push(param);
+            if self.accept(TokenKind::Comma).is_none() {
+                break;
+            }
+        }
+        self.push_list(params)
+    }
+
+    fn parse_closure_ret(&mut self) -> Option<&'b Ast<'b>> {
+        let arrow = self.accept(TokenKind::Arrow)?;
+        let ret = self.parse_type_operand(&arrow);
+        Some(self.push(ret))
+    }
+
+    fn parse_closure(&mut self) -> Ast<'b> {
+        let open = self.advance();
+        let (params, close) = match open.kind {
+            TokenKind::LogicalOr => (&[][..], Token::error()),
+            _ => {
+                let params = self.parse_closure_params(TokenKind::BitOr);
+                let Some(close) = self.accept(TokenKind::BitOr) else {
+                    error!(self, &self.token, "expected {} to close the closure parameters, found {}", TokenKind::BitOr, self.token.kind);
+                    return Ast::error(open)
+                };
+                (params, close)
+            }
+        };
+
+        let ret = self.parse_closure_ret();
+        let body = match (self.accept(TokenKind::BraceOpen), ret) {
+            (Some(brace), _) => self.finish_block(brace),
+            (None, None) => self.parse_operand(&open, 0),
+            (None, Some(_)) => {
+                error!(self, &self.token, "expected {} to begin the closure body, a closure with a return type needs a block body", TokenKind::BraceOpen);
+                Ast::error(open.clone())
+            }
+        };
+
+        let signature = self.push(Ast::Signature(open, params, close, ret));
+        Ast::Closure(None, signature, self.push(body))
+    }
+
+    fn finish_fn_closure(&mut self, kw: Token) -> Ast<'b> {
+        let Some(open) = self.accept(TokenKind::ParenOpen) else {
+            error!(self, &self.token, "expected {} to begin the parameter list, found {}", TokenKind::ParenOpen, self.token.kind);
+            return Ast::error(kw)
+        };
+
+        self.nesting += 1;
+        self.skip_newlines();
+        let params = self.parse_closure_params(TokenKind::ParenClose);
+        self.nesting -= 1;
+        let close = match self.accept(TokenKind::ParenClose) {
+            Some(close) => close,
+            None => {
+                error!(self, &self.token, "expected {} or {} in parameter list, found {}", TokenKind::Comma, TokenKind::ParenClose, self.token.kind);
+                return Ast::error(kw)
+            }
+        };
+
+        let ret = self.parse_closure_ret();
+        let body = self.parse_body(&kw);
+        let signature = self.push(Ast::Signature(open, params, close, ret));
+        Ast::Closure(Some(kw), signature, self.push(body))
+    }
+
+    fn finish_field_patterns(&mut self, base: Ast<'b>, open: Token) -> Ast<'b> {
+        let mut fields = self.begin_list();
+
+        self.nesting += 1;
+        self.skip_newlines();
+        while self.token.kind != TokenKind::BraceClose {
+            if let Some(rest) = self.accept(TokenKind::DotDot) {
+                fields.push(Ast::Rest(rest));
+                if self.token.kind == TokenKind::BraceClose {
+                    break;
+                }
+                error!(self, &self.token, "{} must be the last entry in a struct pattern, found {}", TokenKind::DotDot, self.token.kind);
+                if self.accept(TokenKind::Comma).is_none() {
+                    break;
+                }
+                continue;
+            }
+
+            let Some(name) = self.accept(TokenKind::Ident) else {
+                error!(self, &self.token, "expected a field name, found {}", self.token.kind);
+                break;
+            };
+            let pattern = match self.accept(TokenKind::Colon) {
+                Some(colon) => {
+                    let pattern = self.parse_pattern_operand(&colon);
+                    Some(self.push(pattern))
+                }
+                None => None,
+            };
+            fields.push(Ast::FieldPattern(name, pattern));
+
+            if self.accept(TokenKind::Comma).is_none() {
+                break;
+            }
+        }
+        self.nesting -= 1;
+
+        let Some(close) = self.accept(TokenKind::BraceClose) else {
+            error!(self, &self.token, "expected {} to close the struct pattern, found {}", TokenKind::BraceClose, self.token.kind);
+            return Ast::error(open)
+        };
+        Ast::StructPattern(self.push(base), open, self.push_list(fields), close)
+    }
+
+    fn finish_tuple_pattern(&mut self, open: Token) -> Ast<'b> {
+        let mut items = self.begin_list();
+        let mut trailing = false;
+
+        self.nesting += 1;
+        self.skip_newlines();
+        while self.token.kind != TokenKind::ParenClose {
+            let Some(item) = self.parse_pattern() else {
+                error!(self, &self.token, "expected a pattern, found {}", self.token.kind);
+                break;
+            };
+            items.push(item);
+            trailing = self.accept(TokenKind::Comma).is_some();
+            if !trailing {
+                break;
+            }
+        }
+        self.nesting -= 1;
+
+        let Some(close) = self.accept(TokenKind::ParenClose) else {
+            error!(self, &self.token, "expected {} to close {}, found {}", TokenKind::ParenClose, TokenKind::ParenOpen, self.token.kind);
+            return Ast::error(open)
+        };
+        match (self.push_list(items), trailing) {
+            ([item], false) => Ast::Paren(open, item, close),
+            (items, _) => Ast::TuplePattern(open, items, close),
+        }
+    }
+
+    fn parse_primary_pattern(&mut self) -> Option<Ast<'b>> {
+        let pattern = match self.token.kind {
+            TokenKind::Underscore => Ast::Wildcard(self.advance()),
+            TokenKind::Integer
+            | TokenKind::Float
+            | TokenKind::String
+            | TokenKind::Char
+            | TokenKind::KeywordTrue
+            | TokenKind::KeywordFalse
+            | TokenKind::KeywordNull => Ast::Literal(self.advance()),
+            TokenKind::Sub => {
+                let op = self.advance();
+                if !matches!(self.token.kind, TokenKind::Integer | TokenKind::Float) {
+                    error!(self, &self.token, "expected a number after {} in a pattern, found {}", op.kind, self.token.kind);
+                    return Some(Ast::error(op))
+                }
+                let literal = Ast::Literal(self.advance());
+                Ast::Unop(op, self.push(literal))
+            }
+            TokenKind::Ident => {
+                let name = self.advance();
+                let mut path = Ast::Name(name.clone());
+                while let Some(sep) = self.accept(TokenKind::ColonColon) {
+                    let Some(name) = self.accept(TokenKind::Ident) else {
+                        error!(self, &self.token, "expected a name after {}, found {}", sep.kind, self.token.kind);
+                        return Some(Ast::error(sep))
+                    };
+                    path = Ast::Path(self.push(path), sep, name);
+                }
+                match (self.accept(TokenKind::BraceOpen), path) {
+                    (Some(open), path) => self.finish_field_patterns(path, open),
+                    (None, Ast::Name(_)) => Ast::Bind(name),
+                    (None, path) => path,
+                }
+            }
+            TokenKind::ParenOpen => {
+                let open = self.advance();
+                self.finish_tuple_pattern(open)
+            }
+            _ => return None,
+        };
+        Some(pattern)
+    }
+
+    fn parse_range_pattern(&mut self) -> Option<Ast<'b>> {
+        let lo = self.parse_primary_pattern()?;
+        if !matches!(self.token.kind, TokenKind::DotDot | TokenKind::DotDotEq) {
+            return Some(lo)
+        }
+        let op = self.advance();
+        let hi = match self.parse_primary_pattern() {
+            Some(hi) => hi,
+            None => {
+                error!(self, &self.token, "expected the end of the range after {}, found {}", op.kind, self.token.kind);
+                Ast::error(op.clone())
+            }
+        };
+        Some(Ast::RangePattern(self.push(lo), op, self.push(hi)))
+    }
+
+    fn parse_pattern(&mut self) -> Option<Ast<'b>> {
+        let first = self.parse_range_pattern()?;
+        if self.token.kind != TokenKind::BitOr {
+            return Some(first)
+        }
+
+        let mut alternatives = self.begin_list();
+        alternatives.push(first);
+        while let Some(bar) = self.accept(TokenKind::BitOr) {
+            self.skip_newlines();
+            let pattern = match self.parse_range_pattern() {
+                Some(pattern) => pattern,
+                None => {
+                    error!(self, &self.token, "expected a pattern after {}, found {}", bar.kind, self.token.kind);
+                    Ast::error(bar)
+                }
+            };
+            alternatives.push(pattern);
+        }
+        Some(Ast::OrPattern(self.push_list(alternatives)))
+    }
+
+    fn parse_pattern_operand(&mut self, after: &Token) -> Ast<'b> {
+        match self.parse_pattern() {
+            Some(pattern) => pattern,
+            None => {
+                error!(self, &self.token, "expected a pattern after {}, found {}", after.kind, self.token.kind);
+                Ast::error(after.clone())
+            }
+        }
+    }
+
+    fn parse_arm(&mut self) -> Option<Ast<'b>> {
+        let Some(pattern) = self.parse_pattern() else {
+            error!(self, &self.token, "expected a pattern, found {}", self.token.kind);
+            return None
+        };
+
+        let guard = match self.accept(TokenKind::KeywordIf) {
+            Some(kw) => {
+                let guard = self.parse_operand(&kw, 0);
+                Some(self.push(guard))
+            }
+            None => None,
+        };
+
+        let Some(arrow) = self.accept(TokenKind::FatArrow) else {
+            error!(self, &self.token, "expected {} after the match pattern, found {}", TokenKind::FatArrow, self.token.kind);
+            return None
+        };
+
+        self.skip_newlines();
+        let body = match self.accept(TokenKind::BraceOpen) {
+            Some(open) => self.finish_block(open),
+            None => self.parse_operand(&arrow, 0),
+        };
+        Some(Ast::Arm(self.push(pattern), guard, arrow, self.push(body)))
+    }
+
+    fn finish_match(&mut self, kw: Token) -> Ast<'b> {
+        let scrutinee = self.parse_operand(&kw, 0);
+        let Some(open) = self.accept(TokenKind::BraceOpen) else {
+            error!(self, &self.token, "expected {} to begin the match arms, found {}", TokenKind::BraceOpen, self.token.kind);
+            return Ast::error(kw)
+        };
+
+        // Arms are separated by newlines even when the match is nested in parentheses.
+        let nesting = std::mem::take(&mut self.nesting);
+        let mut arms = self.begin_list();
+
+        self.skip_newlines();
+        let close = loop {
+            if self.token.kind == TokenKind::BraceClose {
+                self.nesting = nesting;
+                break self.advance();
+            }
+            if self.token.kind == TokenKind::End {
+                error!(self, &open, "unclosed match");
+                self.nesting = nesting;
+                break Token::error();
+            }
+
+            let start = self.token.span;
+            self.reported.set(false);
+            match self.parse_arm() {
+                Some(arm) => {
+                    arms.push(arm);
+                    if self.accept(TokenKind::Comma).is_none() && !self.at_stmt_end() {
+                        self.expect_tokens(&[TokenKind::Newline, TokenKind::BraceClose]);
+                        if !self.reported.get() {
+                            self.unexpected();
+                        }
+                        let start = self.token.span;
+                        arms.push(self.recover(start, STMT_SYNC));
+                    }
+                }
+                None => arms.push(self.recover(start, STMT_SYNC)),
+            }
+            self.skip_newlines();
+        };
+
+        Ast::Match(kw, self.push(scrutinee), open, self.push_list(arms), close)
+    }
+
+    fn finish_call(&mut self, callee: Ast<'b>, open: Token) -> Ast<'b> {
+        let mut args = self.begin_list();
+
+        self.nesting += 1;
+        self.skip_newlines();
+        while self.token.kind != TokenKind::ParenClose {
+            let Some(arg) = self.parse_expr() else {
+                error!(self, &self.token, "expected an argument, found {}", self.token.kind);
+                break;
+            };
+            args.push(arg);
+            if self.accept(TokenKind::Comma).is_none() && self.token.kind != TokenKind::ParenClose {
+                error!(self, &self.token, "unclosed argument list, expected {} or {}, found {}", TokenKind::Comma, TokenKind::ParenClose, self.token.kind);
+                break;
+            }
+        }
+        self.nesting -= 1;
+        let close = self.accept(TokenKind::ParenClose).unwrap_or_else(Token::error);
+
+        Ast::Call(self.push(callee), open, self.push_list(args), close)
+    }
+
+    fn finish_index(&mut self, base: Ast<'b>, open: Token) -> Ast<'b> {
+        self.nesting += 1;
+        self.skip_newlines();
+        let index = self.parse_operand(&open, 0);
+        self.nesting -= 1;
+
+        let Some(close) = self.accept(TokenKind::BracketClose) else {
+            error!(self, &self.token, "expected {} to close {}, found {}", TokenKind::BracketClose, TokenKind::BracketOpen, self.token.kind);
+            return Ast::error(open)
+        };
+        Ast::Index(self.push(base), open, self.push(index), close)
+    }
+
+    fn parse_postfix(&mut self) -> Option<Ast<'b>> {
+        let mut expr = self.parse_atom()?;
         loop {
-            match self.token.kind {
-                TokenKind::Add => {
-                    let op = self.advance();
-                    let rhs = self.parse_atom()?;
-                    let l = self.push(lhs);
-                    let r = self.push(rhs);
-                    lhs = Ast::Binop(op, l, r);
-                },
-                _ => { break },
+            expr = match self.token.kind {
+                TokenKind::ParenOpen => {
+                    let open = self.advance();
+                    self.finish_call(expr, open)
+                }
+                TokenKind::BracketOpen => {
+                    let open = self.advance();
+                    self.finish_index(expr, open)
+                }
+                TokenKind::ColonColon => {
+                    let sep = self.advance();
+                    if let Some(open) = self.accept(TokenKind::Less) {
+                        self.finish_generic_args(expr, open)
+                    } else if let Some(name) = self.accept(TokenKind::Ident) {
+                        Ast::Path(self.push(expr), sep, name)
+                    } else {
+                        error!(self, &self.token, "expected a name or {} after {}, found {}", TokenKind::Less, sep.kind, self.token.kind);
+                        return Some(Ast::error(sep))
+                    }
+                }
+                TokenKind::Dot => {
+                    let dot = self.advance();
+                    let Some(name) = self.accept(TokenKind::Ident) else {
+                        error!(self, &self.token, "expected a member name after {}, found {}", TokenKind::Dot, self.token.kind);
+                        return Some(Ast::error(dot))
+                    };
+                    Ast::Member(self.push(expr), dot, name)
+                }
+                _ => return Some(expr),
+            };
+        }
+    }
+
+    fn parse_unary(&mut self) -> Option<Ast<'b>> {
+        if UnaryOp::from_token(self.token.kind).is_some() {
+            let op = self.advance();
+            let expr = self.parse_operand(&op, UNARY_PRECEDENCE);
+            return Some(Ast::Unop(op, self.push(expr)))
+        }
+        self.parse_postfix()
+    }
+
+    fn parse_operand(&mut self, op: &Token, min_prec: u8) -> Ast<'b> {
+        match self.parse_binary(min_prec) {
+            Some(expr) => expr,
+            None => {
+                error!(self, &self.token, "expected an expression after {}, found {}", op.kind, self.token.kind);
+                Ast::error(op.clone())
+            }
+        }
+    }
+
+    fn parse_binary(&mut self, min_prec: u8) -> Option<Ast<'b>> {
+        let mut lhs = self.parse_unary()?;
+        let mut chained: Option<Token> = None;
+
+        while let Some(op) = BinaryOp::from_token(self.token.kind) {
+            let prec = op.precedence();
+            if prec < min_prec {
+                break;
             }
+
+            let token = self.advance();
+            let next_prec = match op.associativity() {
+                Associativity::Right => prec,
+                Associativity::Left => prec + 1,
+                Associativity::None => {
+                    match &chained {
+                        Some(prev) if prev.kind == TokenKind::Less && token.kind == TokenKind::Greater => {
+                            error!(self, &token, "comparison operators cannot be chained, use `::<` to pass generic arguments in an expression");
+                        }
+                        Some(prev) => {
+                            error!(self, &token, "comparison operators cannot be chained, {} follows {} without parentheses", token.kind, prev.kind);
+                        }
+                        None => {}
+                    }
+                    chained = Some(token.clone());
+                    prec + 1
+                }
+            };
+
+            self.skip_newlines();
+            let rhs = self.parse_operand(&token, next_prec);
+            let l = self.push(lhs);
+            let r = self.push(rhs);
+            lhs = Ast::Binop(token, l, r);
         }
         Some(lhs)
     }
 
     fn parse_expr(&mut self) -> Option<Ast<'b>> {
-        self.parse_term()
+        self.parse_binary(0)
     }
 
-    fn finish_class(&mut self, kw: Token) -> Option<Ast<'b>> {
+    fn parse_generic_params(&mut self) -> Option<&'b Ast<'b>> {
+        let open = self.accept(TokenKind::Less)?;
+        let mut params = self.begin_list();
+
+        self.nesting += 1;
+        self.skip_newlines();
+        while !self.at_close_angle() {
+            let Some(name) = self.accept(TokenKind::Ident) else {
+                error!(self, &self.token, "expected a type parameter name, found {}", self.token.kind);
+                while !self.at_close_angle() && !matches!(self.token.kind, TokenKind::ParenOpen | TokenKind::BraceOpen | TokenKind::End) {
+                    self.advance();
+                }
+                break;
+            };
+
+            let mut bounds = self.begin_list();
+            if let Some(colon) = self.accept(TokenKind::Colon) {
+                let mut sep = colon;
+                loop {
+                    bounds.push(self.parse_type_operand(&sep));
+                    match self.accept(TokenKind::Add) {
+                        Some(add) => sep = add,
+                        None => break,
+                    }
+                }
+            }
+            params.push(Ast::GenericParam(name, self.push_list(bounds)));
+
+            if self.accept(TokenKind::Comma).is_none() {
+                break;
+            }
+        }
+        self.nesting -= 1;
+
+        let close = match self.accept_close_angle() {
+            Some(close) => close,
+            None => {
+                error!(self, &self.token, "expected {} to close the type parameters, found {}", TokenKind::Greater, self.token.kind);
+                Token::error()
+            }
+        };
+        let params = self.push_list(params);
+        Some(self.push(Ast::Generics(open, params, close)))
+    }
+
+    fn parse_base(&mut self, kw: &Token) -> Option<&'b Ast<'b>> {
+        let colon = self.accept(TokenKind::Colon)?;
+        if kw.kind == TokenKind::KeywordStruct {
+            error!(self, &colon, "a struct cannot inherit from a base class, only a class can");
+        }
+
+        let base = match self.parse_type() {
+            Some(base) => base,
+            None => {
+                error!(self, &self.token, "expected a base class after {}, found {}", colon.kind, self.token.kind);
+                Ast::error(colon)
+            }
+        };
+
+        if let Some(comma) = self.accept(TokenKind::Comma) {
+            error!(self, &comma, "a class can have at most one base class");
+            while !matches!(self.token.kind, TokenKind::BraceOpen | TokenKind::Newline | TokenKind::End) {
+                self.advance();
+            }
+        }
+        Some(self.push(base))
+    }
+
+    fn finish_class(&mut self, outer_docs: Vec<Token>, vis: Option<Token>, kw: Token) -> Option<Ast<'b>> {
         let Some(name) = self.accept(TokenKind::Ident) else {
             error!(self, &self.token, "expected name for class");
             return None
         };
 
+        let generics = self.parse_generic_params();
+        let base = self.parse_base(&kw);
+
         self.skip_newlines();
         if self.accept(TokenKind::BraceOpen).is_none() {
-            error!(self, &self.token, "expected '{{' following a class declaration");
+            error!(self, &self.token, "expected {} following a class declaration", TokenKind::BraceOpen);
             return None
         };
 
+        let mut docs = outer_docs;
         let mut decls = self.begin_list();
 
         self.skip_newlines();
         while self.accept(TokenKind::BraceClose).is_none() {
             self.skip_newlines();
-            if self.accept(TokenKind::End).is_some() {
+            self.parse_docs(&mut docs, TokenKind::InnerDocComment);
+            if self.token.kind == TokenKind::End {
                 error!(self, &name, "unclosed class");
                 break;
             }
+            if self.token.kind == TokenKind::BraceClose {
+                continue;
+            }
 
-            if let Some(decl) = self.parse_decl() {
-                decls.push(decl);
-            } else {
-                error!(self, &self.token, "expected a declaration");
-                self.recover_decl();
+            let start = self.token.span;
+            self.reported.set(false);
+            match self.parse_member() {
+                Some(decl) => {
+                    let is_field = matches!(decl, Ast::Field(..));
+                    decls.push(decl);
+                    if is_field && let Some(junk) = self.finish_stmt() {
+                        decls.push(junk);
+                    }
+                }
+                None => decls.push(self.recover(start, MEMBER_SYNC)),
             }
             self.skip_newlines();
         };
 
-        Some(Ast::ClassDecl(kw, name, self.push_list(decls)))
+        let docs = self.bump.alloc_slice_clone(&docs);
+        Some(Ast::ClassDecl(docs, vis, kw, name, generics, base, self.push_list(decls)))
     }
 
-    fn parse_decl(&mut self) -> Option<Ast<'b>> {
+    fn finish_field(&mut self, docs: Vec<Token>, vis: Option<Token>, name: Token) -> Ast<'b> {
+        let ty = match self.parse_type() {
+            Some(ty) => ty,
+            None => {
+                error!(self, &self.token, "expected a type for field, found {}", self.token.kind);
+                Ast::error(name.clone())
+            }
+        };
+
+        let init = match self.accept(TokenKind::Assign) {
+            Some(assign) => {
+                self.skip_newlines();
+                let init = self.parse_operand(&assign, 0);
+                Some(self.push(init))
+            }
+            None => None,
+        };
+
+        let docs = self.bump.alloc_slice_clone(&docs);
+        Ast::Field(docs, vis, name, self.push(ty), init)
+    }
+
+    fn parse_member(&mut self) -> Option<Ast<'b>> {
+        let mut docs = Vec::new();
+        self.parse_docs(&mut docs, TokenKind::DocComment);
+        let vis = self.accept(TokenKind::KeywordPub);
+
         match self.token.kind {
             TokenKind::KeywordClass | TokenKind::KeywordStruct => {
                 let token = self.advance();
-                self.finish_class(token)
+                self.finish_class(docs, vis, token)
+            }
+            TokenKind::KeywordFn => {
+                let token = self.advance();
+                self.finish_fn(docs, vis, token)
+            }
+            TokenKind::Ident => {
+                let name = self.advance();
+                if self.accept(TokenKind::Colon).is_some() {
+                    return Some(self.finish_field(docs, vis, name))
+                }
+                error!(self, &name, "expressions are not allowed in a class body, declare a field with `name: Type` or move the code into a method");
+                None
+            }
+            TokenKind::KeywordLet | TokenKind::KeywordVar => {
+                error!(self, &self.token, "{} cannot be used in a class body, declare a field with `name: Type`", self.token.kind);
+                None
+            }
+            TokenKind::KeywordImport => {
+                error!(self, &self.token, "imports are only allowed at the top level of a file");
+                None
+            }
+            _ => {
+                self.expect_tokens(&[TokenKind::Ident, TokenKind::KeywordFn, TokenKind::KeywordClass, TokenKind::KeywordStruct]);
+                self.unexpected();
+                None
+            }
+        }
+    }
+
+    fn at_close_angle(&self) -> bool {
+        matches!(self.token.kind, TokenKind::Greater | TokenKind::ShiftRight | TokenKind::GreaterEquals | TokenKind::ShiftRightAssign)
+    }
+
+    // Accept a `>` closing a generic argument list, splitting it off `>>`, `>=` or `>>=`.
+    fn accept_close_angle(&mut self) -> Option<Token> {
+        let rest = match self.token.kind {
+            TokenKind::Greater => return Some(self.advance()),
+            TokenKind::ShiftRight => TokenKind::Greater,
+            TokenKind::GreaterEquals => TokenKind::Assign,
+            TokenKind::ShiftRightAssign => TokenKind::GreaterEquals,
+            _ => return None,
+        };
+
+        let span = self.token.span;
+        let mid = span.begin() + 1;
+        let first = Token {
+            kind: TokenKind::Greater,
+            span: SourceSpan::new(span.file(), span.begin(), mid),
+            value: TokenValue::None,
+            trivia: Trivia { leading: self.token.trivia.leading, trailing: 0 },
+        };
+        self.token.kind = rest;
+        self.token.span = SourceSpan::new(span.file(), mid, span.end());
+        self.token.trivia.leading = 0;
+        Some(first)
+    }
+
+    fn finish_generic_args(&mut self, base: Ast<'b>, open: Token) -> Ast<'b> {
+        let mut args = self.begin_list();
+
+        self.nesting += 1;
+        self.skip_newlines();
+        while !self.at_close_angle() {
+            let Some(arg) = self.parse_type() else {
+                error!(self, &self.token, "expected a type argument, found {}", self.token.kind);
+                break;
+            };
+            args.push(arg);
+            if self.accept(TokenKind::Comma).is_none() {
+                break;
+            }
+        }
+        self.nesting -= 1;
+
+        let Some(close) = self.accept_close_angle() else {
+            error!(self, &self.token, "expected {} to close the generic arguments, found {}", TokenKind::Greater, self.token.kind);
+            return Ast::Generic(self.push(base), open, self.push_list(args), Token::error())
+        };
+        Ast::Generic(self.push(base), open, self.push_list(args), close)
+    }
+
+    fn parse_type_operand(&mut self, after: &Token) -> Ast<'b> {
+        match self.parse_type() {
+            Some(ty) => ty,
+            None => {
+                error!(self, &self.token, "expected a type after {}, found {}", after.kind, self.token.kind);
+                Ast::error(after.clone())
+            }
+        }
+    }
+
+    // Parses the types of a parenthesized list, returning whether it ended in a comma.
+    fn finish_type_list(&mut self, open: &Token) -> (&'b [Ast<'b>], bool, Token) {
+        let mut types = self.begin_list();
+        let mut trailing = false;
+
+        self.nesting += 1;
+        self.skip_newlines();
+        while self.token.kind != TokenKind::ParenClose {
+            let Some(ty) = self.parse_type() else {
+                error!(self, &self.token, "expected a type, found {}", self.token.kind);
+                break;
+            };
+            types.push(ty);
+            trailing = self.accept(TokenKind::Comma).is_some();
+            if !trailing {
+                break;
             }
-            _ => self.parse_expr(),
         }
+        self.nesting -= 1;
+
+        let close = match self.accept(TokenKind::ParenClose) {
+            Some(close) => close,
+            None => {
+                error!(self, &self.token, "expected {} to close {}, found {}", TokenKind::ParenClose, open.kind, self.token.kind);
+                Token::error()
+            }
+        };
+        (self.push_list(types), trailing, close)
     }
 
-    fn recover_decl(&mut self) {
+    fn parse_type(&mut self) -> Option<Ast<'b>> {
+        let mut ty = match self.token.kind {
+            TokenKind::Ident => {
+                let mut ty = Ast::Name(self.advance());
+                while let Some(sep) = self.accept(TokenKind::ColonColon) {
+                    let Some(name) = self.accept(TokenKind::Ident) else {
+                        error!(self, &self.token, "expected a name after {}, found {}", sep.kind, self.token.kind);
+                        return Some(Ast::error(sep))
+                    };
+                    ty = Ast::Path(self.push(ty), sep, name);
+                }
+                if let Some(open) = self.accept(TokenKind::Less) {
+                    ty = self.finish_generic_args(ty, open);
+                }
+                ty
+            }
+            TokenKind::BracketOpen => {
+                let open = self.advance();
+                self.nesting += 1;
+                self.skip_newlines();
+                let elem = self.parse_type_operand(&open);
+                self.nesting -= 1;
+                let Some(close) = self.accept(TokenKind::BracketClose) else {
+                    error!(self, &self.token, "expected {} to close the array type, found {}", TokenKind::BracketClose, self.token.kind);
+                    return Some(Ast::error(open))
+                };
+                Ast::ArrayType(open, self.push(elem), close)
+            }
+            TokenKind::ParenOpen => {
+                let open = self.advance();
+                match self.finish_type_list(&open) {
+                    ([ty], false, close) => Ast::Paren(open, ty, close),
+                    (types, _, close) => Ast::TupleType(open, types, close),
+                }
+            }
+            TokenKind::KeywordFn => {
+                let kw = self.advance();
+                let Some(open) = self.accept(TokenKind::ParenOpen) else {
+                    error!(self, &self.token, "expected {} after {} in a function type, found {}", TokenKind::ParenOpen, kw.kind, self.token.kind);
+                    return Some(Ast::error(kw))
+                };
+                let (params, _, close) = self.finish_type_list(&open);
+                let ret = match self.accept(TokenKind::Arrow) {
+                    Some(arrow) => {
+                        let ret = self.parse_type_operand(&arrow);
+                        Some(self.push(ret))
+                    }
+                    None => None,
+                };
+                Ast::FnType(kw, open, params, close, ret)
+            }
+            _ => return None,
+        };
+
+        while let Some(question) = self.accept(TokenKind::Question) {
+            ty = Ast::OptionalType(self.push(ty), question);
+        }
+        Some(ty)
+    }
+
+    fn parse_param(&mut self) -> Ast<'b> {
+        let Some(name) = self.accept(TokenKind::Ident) else {
+            error!(self, &self.token, "expected a parameter name, found {}", self.token.kind);
+            while !matches!(self.token.kind, TokenKind::Comma | TokenKind::ParenClose | TokenKind::BraceOpen | TokenKind::End) {
+                self.advance();
+            }
+            return Ast::error(self.token.clone())
+        };
+
+        if self.accept(TokenKind::Colon).is_none() {
+            error!(self, &name, "missing type for parameter, expected {} followed by a type", TokenKind::Colon);
+            return Ast::Param(name.clone(), self.push(Ast::error(name)))
+        }
+
+        let ty = match self.parse_type() {
+            Some(ty) => ty,
+            None => {
+                error!(self, &self.token, "expected a type for parameter, found {}", self.token.kind);
+                Ast::error(name.clone())
+            }
+        };
+        Ast::Param(name, self.push(ty))
+    }
+
+    fn parse_signature(&mut self) -> Option<Ast<'b>> {
+        let Some(open) = self.accept(TokenKind::ParenOpen) else {
+            error!(self, &self.token, "expected {} to begin the parameter list, found {}", TokenKind::ParenOpen, self.token.kind);
+            return None
+        };
+
+        let mut params = self.begin_list();
+        self.nesting += 1;
+        self.skip_newlines();
+        while self.token.kind != TokenKind::ParenClose {
+            if self.token.kind == TokenKind::End {
+                error!(self, &open, "unclosed parameter list");
+                break;
+            }
+            if let Some(comma) = self.accept(TokenKind::Comma) {
+                error!(self, &comma, "duplicated {} in parameter list", TokenKind::Comma);
+                continue;
+            }
+
+            params.push(self.parse_param());
+            if self.accept(TokenKind::Comma).is_none() && self.token.kind != TokenKind::ParenClose {
+                error!(self, &self.token, "expected {} or {} in parameter list, found {}", TokenKind::Comma, TokenKind::ParenClose, self.token.kind);
+                break;
+            }
+        }
+        self.nesting -= 1;
+        let close = self.accept(TokenKind::ParenClose).unwrap_or_else(Token::error);
+
+        let ret = match self.accept(TokenKind::Arrow) {
+            Some(arrow) => match self.parse_type() {
+                Some(ty) => Some(self.push(ty)),
+                None => {
+                    error!(self, &self.token, "expected a return type after {}, found {}", arrow.kind, self.token.kind);
+                    Some(self.push(Ast::error(arrow)))
+                }
+            },
+            None => None,
+        };
+
+        Some(Ast::Signature(open, self.push_list(params), close, ret))
+    }
+
+    fn finish_block(&mut self, open: Token) -> Ast<'b> {
+        // Newlines separate items inside a block even when nested in parentheses.
+        let nesting = std::mem::take(&mut self.nesting);
+        let mut items = self.begin_list();
+
+        self.skip_newlines();
+        let close = loop {
+            if self.token.kind == TokenKind::BraceClose {
+                self.nesting = nesting;
+                break self.advance();
+            }
+            if self.token.kind == TokenKind::End {
+                error!(self, &open, "unclosed block");
+                self.nesting = nesting;
+                break Token::error();
+            }
+
+            let start = self.token.span;
+            self.reported.set(false);
+            match self.parse_stmt() {
+                Some(stmt) => {
+                    items.push(stmt);
+                    if let Some(junk) = self.finish_stmt() {
+                        items.push(junk);
+                    }
+                }
+                // A newline ends the `if` statement, so `else` can't start the next line.
+                None if self.token.kind == TokenKind::KeywordElse && matches!(items.last(), Some(Ast::If(.., None))) => {
+                    error!(self, &self.token, "`else` must be on the same line as the `}}` closing the `if` body");
+                    items.push(self.recover(start, STMT_SYNC));
+                }
+                None => {
+                    error!(self, &self.token, "expected a statement, found {}", self.token.kind);
+                    items.push(self.recover(start, STMT_SYNC));
+                }
+            }
+            self.skip_newlines();
+        };
+
+        Ast::Block(open, self.push_list(items), close)
+    }
+
+    fn at_stmt_end(&self) -> bool {
+        matches!(self.token.kind, TokenKind::Newline | TokenKind::Semicolon | TokenKind::BraceClose | TokenKind::End)
+    }
+
+    // Returns an error node covering anything left over after the statement.
+    fn finish_stmt(&mut self) -> Option<Ast<'b>> {
+        if self.accept(TokenKind::Semicolon).is_some() || self.at_stmt_end() {
+            return None;
+        }
+        self.expect_tokens(&[TokenKind::Newline, TokenKind::BraceClose]);
+        // Don't pile a second error onto a statement that already failed to parse.
+        if !self.reported.get() {
+            self.unexpected();
+        }
+        let start = self.token.span;
+        Some(self.recover(start, STMT_SYNC))
+    }
+
+    // Skips to the next token in `sync` outside of nested braces and returns an
+    // error node spanning everything from `start` that was skipped.
+    fn recover(&mut self, start: SourceSpan, sync: &[TokenKind]) -> Ast<'b> {
+        let mut depth = 0u32;
         loop {
-            match self.token.kind {
-                TokenKind::Newline | TokenKind::End => {
-                    self.advance();
-                    break;
+            let kind = self.token.kind;
+            // Always make progress unless the enclosing construct can handle the token.
+            let progress = self.last_end > start.begin()
+                || matches!(kind, TokenKind::Newline | TokenKind::Semicolon | TokenKind::BraceClose);
+            if kind == TokenKind::End || (depth == 0 && progress && sync.contains(&kind)) {
+                break;
+            }
+            match self.advance().kind {
+                TokenKind::BraceOpen => depth += 1,
+                TokenKind::BraceClose => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+
+        let end = self.last_end.max(start.begin());
+        let token = Token {
+            kind: TokenKind::Error,
+            span: SourceSpan::new(start.file(), start.begin(), end),
+            value: TokenValue::None,
+            trivia: Trivia::default(),
+        };
+        if sync.contains(&TokenKind::Semicolon) && self.token.kind == TokenKind::Semicolon {
+            self.advance();
+        }
+        Ast::Error(token)
+    }
+
+    fn parse_body(&mut self, kw: &Token) -> Ast<'b> {
+        match self.accept(TokenKind::BraceOpen) {
+            Some(open) => self.finish_block(open),
+            None => {
+                error!(self, &self.token, "expected {} to begin the body of {}, found {}", TokenKind::BraceOpen, kw.kind, self.token.kind);
+                Ast::error(self.token.clone())
+            }
+        }
+    }
+
+    fn parse_condition(&mut self, kw: &Token) -> Ast<'b> {
+        match self.parse_expr() {
+            Some(expr) => expr,
+            None => {
+                error!(self, &self.token, "expected a condition after {}, found {}", kw.kind, self.token.kind);
+                Ast::error(kw.clone())
+            }
+        }
+    }
+
+    fn finish_let(&mut self, kw: Token) -> Option<Ast<'b>> {
+        let Some(name) = self.accept(TokenKind::Ident) else {
+            error!(self, &self.token, "expected a name after {}, found {}", kw.kind, self.token.kind);
+            return Some(self.recover(kw.span, STMT_SYNC))
+        };
+
+        let ty = match self.accept(TokenKind::Colon) {
+            Some(colon) => match self.parse_type() {
+                Some(ty) => Some(self.push(ty)),
+                None => {
+                    error!(self, &self.token, "expected a type after {}, found {}", colon.kind, self.token.kind);
+                    Some(self.push(Ast::error(colon)))
                 }
-                _ => {
-                    self.advance();
+            },
+            None => None,
+        };
+
+        let init = match self.accept(TokenKind::Assign) {
+            Some(assign) => {
+                self.skip_newlines();
+                let init = self.parse_operand(&assign, 0);
+                Some(self.push(init))
+            }
+            None => None,
+        };
+
+        Some(Ast::Let(kw, name, ty, init))
+    }
+
+    fn finish_if(&mut self, kw: Token) -> Ast<'b> {
+        let cond = self.parse_condition(&kw);
+        let then = self.parse_body(&kw);
+
+        let otherwise = match self.accept(TokenKind::KeywordElse) {
+            Some(else_kw) => match self.accept(TokenKind::KeywordIf) {
+                Some(if_kw) => Some(self.finish_if(if_kw)),
+                None => Some(self.parse_body(&else_kw)),
+            },
+            None => None,
+        };
+
+        let otherwise = otherwise.map(|ast| self.push(ast));
+        Ast::If(kw, self.push(cond), self.push(then), otherwise)
+    }
+
+    fn finish_for(&mut self, kw: Token) -> Ast<'b> {
+        let Some(name) = self.accept(TokenKind::Ident) else {
+            error!(self, &self.token, "expected a loop variable after {}, found {}", kw.kind, self.token.kind);
+            return self.recover(kw.span, STMT_SYNC)
+        };
+        let Some(in_kw) = self.accept(TokenKind::KeywordIn) else {
+            error!(self, &self.token, "expected {} after the loop variable, found {}", TokenKind::KeywordIn, self.token.kind);
+            return self.recover(kw.span, STMT_SYNC)
+        };
+
+        let iter = self.parse_operand(&in_kw, 0);
+        let body = self.parse_body(&kw);
+        Ast::For(kw, name, self.push(iter), self.push(body))
+    }
+
+    fn parse_stmt(&mut self) -> Option<Ast<'b>> {
+        match self.token.kind {
+            TokenKind::KeywordLet | TokenKind::KeywordVar => {
+                let kw = self.advance();
+                self.finish_let(kw)
+            }
+            TokenKind::KeywordIf => {
+                let kw = self.advance();
+                Some(self.finish_if(kw))
+            }
+            TokenKind::KeywordWhile => {
+                let kw = self.advance();
+                let cond = self.parse_condition(&kw);
+                let body = self.parse_body(&kw);
+                Some(Ast::While(kw, self.push(cond), self.push(body)))
+            }
+            TokenKind::KeywordFor => {
+                let kw = self.advance();
+                Some(self.finish_for(kw))
+            }
+            TokenKind::KeywordReturn => {
+                let kw = self.advance();
+                let value = match self.at_stmt_end() {
+                    true => None,
+                    false => {
+                        let value = self.parse_operand(&kw, 0);
+                        Some(self.push(value))
+                    }
+                };
+                Some(Ast::Return(kw, value))
+            }
+            TokenKind::KeywordBreak => Some(Ast::Break(self.advance())),
+            TokenKind::KeywordContinue => Some(Ast::Continue(self.advance())),
+            TokenKind::BraceOpen => {
+                let open = self.advance();
+                Some(self.finish_block(open))
+            }
+            _ => self.parse_expr(),
+        }
+    }
+
+    fn finish_fn(&mut self, docs: Vec<Token>, vis: Option<Token>, kw: Token) -> Option<Ast<'b>> {
+        let Some(name) = self.accept(TokenKind::Ident) else {
+            error!(self, &self.token, "expected name for function, found {}", self.token.kind);
+            return None
+        };
+
+        let generics = self.parse_generic_params();
+        let signature = self.parse_signature()?;
+
+        self.skip_newlines();
+        let body = self.parse_body(&kw);
+
+        let docs = self.bump.alloc_slice_clone(&docs);
+        Some(Ast::FnDecl(docs, vis, kw, name, generics, self.push(signature), self.push(body)))
+    }
+
+    fn parse_decl(&mut self) -> Option<Ast<'b>> {
+        let mut docs = Vec::new();
+        self.parse_docs(&mut docs, TokenKind::DocComment);
+        let vis = self.accept(TokenKind::KeywordPub);
+
+        match self.token.kind {
+            TokenKind::KeywordClass | TokenKind::KeywordStruct => {
+                let token = self.advance();
+                self.finish_class(docs, vis, token)
+            }
+            TokenKind::KeywordFn => {
+                let token = self.advance();
+                self.finish_fn(docs, vis, token)
+            }
+            TokenKind::KeywordImport => {
+                if let Some(vis) = &vis {
+                    error!(self, vis, "imports cannot be {}", vis.kind);
+                }
+                let kw = self.advance();
+                self.finish_import(kw)
+            }
+            _ => {
+                if let Some(doc) = docs.first() {
+                    error!(self, doc, "doc comment is not followed by a declaration");
                 }
+                if let Some(vis) = &vis {
+                    error!(self, vis, "{} must be followed by a declaration", vis.kind);
+                }
+                self.expect_tokens(&[TokenKind::KeywordFn, TokenKind::KeywordClass, TokenKind::KeywordStruct, TokenKind::KeywordImport]);
+                let expr = self.parse_expr();
+                if expr.is_none() && !self.reported.get() {
+                    self.expect(Expected::Expression);
+                    self.unexpected();
+                }
+                expr
             }
         }
     }
 
+    fn accept_contextual(&mut self, keyword: ContextualKeyword) -> Option<Token> {
+        if self.token.kind == TokenKind::Ident && self.token.value == TokenValue::Contextual(keyword) {
+            Some(self.advance())
+        } else {
+            None
+        }
+    }
+
+    // `import a.b.c` or `import x from "path"`
+    fn finish_import(&mut self, kw: Token) -> Option<Ast<'b>> {
+        let mut path = Vec::new();
+        loop {
+            let Some(name) = self.accept(TokenKind::Ident) else {
+                error!(self, &self.token, "expected a module name in {}, found {}", kw.kind, self.token.kind);
+                return None
+            };
+            path.push(name);
+            if self.accept(TokenKind::Dot).is_none() {
+                break;
+            }
+        }
+
+        let source = match self.accept_contextual(ContextualKeyword::From) {
+            Some(from) => {
+                let Some(source) = self.accept(TokenKind::String) else {
+                    error!(self, &self.token, "expected a file path string after `from`, found {}", self.token.kind);
+                    return None
+                };
+                if path.len() > 1 {
+                    error!(self, &path[1], "`import ... from` binds a single name, found a dotted module path");
+                }
+                Some(source)
+            }
+            None => None,
+        };
+
+        if !self.at_stmt_end() {
+            error!(self, &self.token, "expected a newline after import, found {}", self.token.kind);
+            return None
+        }
+
+        Some(Ast::Import(kw, self.bump.alloc_slice_clone(&path), source))
+    }
+
     fn parse(&mut self) -> Ast<'b> {
         self.advance();
 
+        let mut docs = Vec::new();
         let mut decls = self.begin_list();
 
         self.skip_newlines();
-        while self.accept(TokenKind::End).is_none() {
-            self.skip_newlines();
-            if let Some(decl) = self.parse_decl() {
-                decls.push(decl);
-            } else {
-                self.recover_decl()
+        while self.token.kind != TokenKind::End {
+            self.parse_docs(&mut docs, TokenKind::InnerDocComment);
+            if self.token.kind == TokenKind::End {
+                break;
+            }
+
+            let start = self.token.span;
+            self.reported.set(false);
+            match self.parse_decl() {
+                Some(decl) => {
+                    // Declarations ending in a block may be followed by another on the same line.
+                    let braced = matches!(decl, Ast::ClassDecl(..) | Ast::FnDecl(..));
+                    decls.push(decl);
+                    // An unclosed group may have swallowed the newline that ended the declaration.
+                    let at_end = matches!(self.token.kind, TokenKind::Newline | TokenKind::End) || self.skipped_newline;
+                    if !braced && self.accept(TokenKind::Semicolon).is_none() && !at_end {
+                        self.expect(Expected::Token(TokenKind::Newline));
+                        if !self.reported.get() {
+                            self.unexpected();
+                        }
+                        let start = self.token.span;
+                        decls.push(self.recover(start, DECL_SYNC));
+                    }
+                }
+                None => decls.push(self.recover(start, DECL_SYNC)),
             }
             self.skip_newlines();
         }
 
-        Ast::Root(self.push_list(decls))
+        Ast::Root(self.bump.alloc_slice_clone(&docs), self.push_list(decls))
     }
 }
 
 impl<'a, 'b> Errors for Parser<'a, 'b> {
     fn push(&self, int_loc: &InternalLocation, loc: &dyn Locatable, message: String, context: Vec<String>) {
+        self.reported.set(true);
         self.errors.push(int_loc, loc, message, context);
     }
+    fn push_warning(&self, int_loc: &InternalLocation, loc: &dyn Locatable, message: String, context: Vec<String>) {
+        self.errors.push_warning(int_loc, loc, message, context);
+    }
 }
 
 pub fn parse(errors: &dyn Errors, tokens: impl Iterator<Item = Token>) -> AstRoot {
     AstRoot::new(|bump| {
         let mut tokens = tokens;
-        let mut parser = Parser::new(errors, &mut tokens, &bump);
+        let mut parser = Parser::new(errors, &mut tokens, bump);
         parser.parse()
     })
 }
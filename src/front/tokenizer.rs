@@ -1,5 +1,10 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::str::{CharIndices, SplitInclusive};
 
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+use unicode_security::{skeleton, MixedScript};
+
 use crate::{ast::*, *};
 
 pub struct Tokenizer<'a> {
@@ -7,24 +12,403 @@ pub struct Tokenizer<'a> {
     pos: usize,
     file: SourceFile,
     errors: &'a dyn Errors,
+    value: TokenValue,
+    lossless: bool,
+    done: bool,
+    confusables: Option<HashMap<String, Vec<String>>>,
+    scalar: bool,
 }
 
-fn is_whitespace(ch: u8) -> bool {
-    match ch {
-        b' ' | b'\t' | b'\r' => true,
-        _ => false,
+fn byte_at(src: &[u8], pos: usize) -> u8 {
+    if pos < src.len() { src[pos] } else { b'\0' }
+}
+
+const fn is_ident_continue(ch: u8) -> bool {
+    matches!(ch, b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_')
+}
+
+const IDENT_CONTINUE: [bool; 256] = {
+    let mut table = [false; 256];
+    let mut ch = 0;
+    while ch < 256 {
+        table[ch] = is_ident_continue(ch as u8);
+        ch += 1;
+    }
+    table
+};
+
+fn is_unicode_ident_start(src: &[u8], pos: usize) -> Option<usize> {
+    let ch = decode_char(src, pos)?;
+    unicode_ident::is_xid_start(ch).then(|| ch.len_utf8())
+}
+
+fn is_unicode_ident_continue(src: &[u8], pos: usize) -> Option<usize> {
+    let ch = decode_char(src, pos)?;
+    unicode_ident::is_xid_continue(ch).then(|| ch.len_utf8())
+}
+
+/// Name of an identifier token used for comparisons, normalized to NFC so that
+/// differently composed spellings of the same identifier compare equal. Raw
+/// identifiers are returned without their `r#` prefix.
+pub fn ident_name(text: &[u8]) -> Cow<'_, str> {
+    let text = String::from_utf8_lossy(text.strip_prefix(b"r#").unwrap_or(text));
+    if text.is_ascii() || is_nfc_quick(text.chars()) == IsNormalized::Yes {
+        return text;
+    }
+    Cow::Owned(text.nfc().collect())
+}
+
+fn is_digit(ch: u8) -> bool {
+    ch.is_ascii_digit()
+}
+
+fn utf8_len(first: u8) -> usize {
+    match first {
+        0xf0.. => 4,
+        0xe0.. => 3,
+        0xc0.. => 2,
+        _ => 1,
+    }
+}
+
+fn decode_char(src: &[u8], pos: usize) -> Option<char> {
+    let end = (pos + utf8_len(*src.get(pos)?)).min(src.len());
+    std::str::from_utf8(&src[pos..end]).ok()?.chars().next()
+}
+
+// Length of the character at `pos`, or 1 for invalid or truncated UTF-8.
+fn char_len(src: &[u8], pos: usize) -> usize {
+    decode_char(src, pos).map_or(1, |ch| ch.len_utf8())
+}
+
+fn read_unicode_escape(src: &[u8], pos: usize) -> (usize, Result<char, &'static str>) {
+    let mut pos = pos + 2;
+    if byte_at(src, pos) != b'{' {
+        return (pos, Err("expected `{` after `\\u`"));
+    }
+    pos += 1;
+
+    let digits_begin = pos;
+    while byte_at(src, pos).is_ascii_hexdigit() {
+        pos += 1;
+    }
+    let digits = &src[digits_begin..pos];
+    if byte_at(src, pos) != b'}' {
+        return (pos, Err("unterminated unicode escape"));
+    }
+    pos += 1;
+
+    if digits.is_empty() || digits.len() > 6 {
+        return (pos, Err("unicode escape must have between 1 and 6 hex digits"));
+    }
+    let value = digits.iter().fold(0, |v, c| v * 16 + (*c as char).to_digit(16).unwrap());
+    match char::from_u32(value) {
+        Some(ch) => (pos, Ok(ch)),
+        None => (pos, Err("invalid unicode scalar value in escape")),
     }
 }
 
-fn token_spelling(tok: TokenKind) -> Option<&'static [u8]> {
-    let spelling: &[u8] = match tok {
-        TokenKind::Assign => b"=",
-        TokenKind::Equals => b"==",
-        TokenKind::BraceOpen => b"(",
-        TokenKind::BraceClose => b")",
-        _ => return None,
+fn read_escape(src: &[u8], pos: usize) -> (usize, Result<char, &'static str>) {
+    if pos + 1 >= src.len() {
+        return (src.len(), Err("unterminated escape sequence"));
+    }
+    let ch = match src[pos + 1] {
+        b'n' => '\n',
+        b't' => '\t',
+        b'r' => '\r',
+        b'0' => '\0',
+        b'\\' => '\\',
+        b'"' => '"',
+        b'\'' => '\'',
+        b'u' => return read_unicode_escape(src, pos),
+        // Invalid UTF-8 after the backslash is skipped one byte at a time.
+        _ => return (pos + 1 + char_len(src, pos + 1), Err("unknown escape sequence")),
     };
-    Some(spelling)
+    (pos + 2, Ok(ch))
+}
+
+fn raw_string_hashes(text: &[u8]) -> usize {
+    text[1..].iter().take_while(|c| **c == b'#').count()
+}
+
+/// Decode the contents of a `String` token, `text` being the full token
+/// including quotes. Invalid escapes are skipped, as the tokenizer has already
+/// reported them.
+pub fn string_value(text: &[u8]) -> String {
+    if text.first() == Some(&b'r') {
+        let hashes = raw_string_hashes(text);
+        let body = text.get(hashes + 2..text.len().saturating_sub(hashes + 1)).unwrap_or_default();
+        return String::from_utf8_lossy(body).into_owned();
+    }
+
+    let end = if text.len() >= 2 && text.ends_with(b"\"") { text.len() - 1 } else { text.len() };
+    let text = &text[..end];
+    let mut result = Vec::new();
+    let mut pos = 1;
+    while pos < text.len() {
+        if text[pos] == b'\\' {
+            let (next, ch) = read_escape(text, pos);
+            if let Ok(ch) = ch {
+                result.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+            pos = next;
+        } else {
+            result.push(text[pos]);
+            pos += 1;
+        }
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+fn is_doc_comment(src: &[u8], pos: usize) -> bool {
+    match byte_at(src, pos + 2) {
+        b'/' => byte_at(src, pos + 3) != b'/',
+        b'!' => true,
+        _ => false,
+    }
+}
+
+fn block_comment_end(src: &[u8], begin: usize) -> Option<usize> {
+    let mut pos = begin + 2;
+    let mut depth = 1;
+
+    while depth > 0 {
+        pos += memchr::memchr2(b'/', b'*', &src[pos..])?;
+        match (src[pos], byte_at(src, pos + 1)) {
+            (b'/', b'*') => {
+                depth += 1;
+                pos += 2;
+            }
+            (b'*', b'/') => {
+                depth -= 1;
+                pos += 2;
+            }
+            _ => pos += 1,
+        }
+    }
+
+    Some(pos)
+}
+
+pub fn split_trivia(source: &[u8], span: SourceSpan) -> Vec<TriviaPiece> {
+    let mut pieces = Vec::new();
+    let mut pos = span.begin();
+
+    while pos < span.end() {
+        let begin = pos;
+        let kind = match (source[pos], byte_at(source, pos + 1)) {
+            _ if pos == 0 && source.starts_with(BYTE_ORDER_MARK) => {
+                pos = BYTE_ORDER_MARK.len();
+                TriviaKind::ByteOrderMark
+            }
+            (b'/', b'/') => {
+                pos = line_end(&source[..span.end()], pos);
+                TriviaKind::LineComment
+            }
+            (b'/', b'*') => {
+                pos = block_comment_end(source, pos).unwrap_or(source.len()).min(span.end());
+                TriviaKind::BlockComment
+            }
+            _ => {
+                while pos < span.end() && is_whitespace(source[pos]) {
+                    pos += 1;
+                }
+                pos = pos.max(begin + 1);
+                TriviaKind::Whitespace
+            }
+        };
+        pieces.push(TriviaPiece {
+            kind,
+            span: SourceSpan::new(span.file(), begin, pos),
+        });
+    }
+
+    pieces
+}
+
+const BYTE_ORDER_MARK: &[u8] = b"\xef\xbb\xbf";
+
+fn is_whitespace(ch: u8) -> bool {
+    matches!(ch, b' ' | b'\t')
+}
+
+fn line_end(src: &[u8], pos: usize) -> usize {
+    match memchr::memchr2(b'\n', b'\r', &src[pos..]) {
+        Some(p) => pos + p,
+        None => src.len(),
+    }
+}
+
+// SWAR byte classification: each function below takes 8 bytes packed in a
+// little-endian `u64` and returns a mask with the high bit of each byte set if
+// the byte is in the class.
+const SWAR_ONES: u64 = 0x0101_0101_0101_0101;
+const SWAR_HIGH: u64 = 0x8080_8080_8080_8080;
+
+fn swar_load(src: &[u8], pos: usize) -> Option<u64> {
+    let bytes = src.get(pos..pos + 8)?;
+    Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn swar_nonzero(x: u64) -> u64 {
+    (((x & !SWAR_HIGH) + !SWAR_HIGH) | x) & SWAR_HIGH
+}
+
+fn swar_equal(x: u64, byte: u8) -> u64 {
+    !swar_nonzero(x ^ (SWAR_ONES * byte as u64)) & SWAR_HIGH
+}
+
+fn swar_whitespace(x: u64) -> u64 {
+    swar_equal(x, b' ') | swar_equal(x, b'\t')
+}
+
+// Number of leading bytes in the class given the class mask of a word.
+fn swar_prefix_len(mask: u64) -> usize {
+    ((!mask & SWAR_HIGH).trailing_zeros() / 8) as usize
+}
+
+const PUNCTUATION: &[(&[u8], TokenKind)] = &[
+    (b"<<=", TokenKind::ShiftLeftAssign),
+    (b">>=", TokenKind::ShiftRightAssign),
+    (b"..=", TokenKind::DotDotEq),
+    (b"==", TokenKind::Equals),
+    (b"!=", TokenKind::NotEquals),
+    (b"<=", TokenKind::LessEquals),
+    (b">=", TokenKind::GreaterEquals),
+    (b"&&", TokenKind::LogicalAnd),
+    (b"||", TokenKind::LogicalOr),
+    (b"<<", TokenKind::ShiftLeft),
+    (b">>", TokenKind::ShiftRight),
+    (b"+=", TokenKind::AddAssign),
+    (b"-=", TokenKind::SubAssign),
+    (b"*=", TokenKind::MulAssign),
+    (b"/=", TokenKind::DivAssign),
+    (b"%=", TokenKind::ModAssign),
+    (b"&=", TokenKind::BitAndAssign),
+    (b"|=", TokenKind::BitOrAssign),
+    (b"^=", TokenKind::BitXorAssign),
+    (b"->", TokenKind::Arrow),
+    (b"=>", TokenKind::FatArrow),
+    (b"::", TokenKind::ColonColon),
+    (b"..", TokenKind::DotDot),
+    (b"=", TokenKind::Assign),
+    (b"<", TokenKind::Less),
+    (b">", TokenKind::Greater),
+    (b"{", TokenKind::BraceOpen),
+    (b"}", TokenKind::BraceClose),
+    (b"(", TokenKind::ParenOpen),
+    (b")", TokenKind::ParenClose),
+    (b"[", TokenKind::BracketOpen),
+    (b"]", TokenKind::BracketClose),
+    (b"+", TokenKind::Add),
+    (b"-", TokenKind::Sub),
+    (b"*", TokenKind::Mul),
+    (b"/", TokenKind::Div),
+    (b"%", TokenKind::Mod),
+    (b"!", TokenKind::Not),
+    (b"~", TokenKind::BitNot),
+    (b"&", TokenKind::BitAnd),
+    (b"|", TokenKind::BitOr),
+    (b"^", TokenKind::BitXor),
+    (b",", TokenKind::Comma),
+    (b".", TokenKind::Dot),
+    (b":", TokenKind::Colon),
+    (b";", TokenKind::Semicolon),
+    (b"?", TokenKind::Question),
+];
+
+const KEYWORDS: &[(&[u8], TokenKind)] = &[
+    (b"_", TokenKind::Underscore),
+    (b"class", TokenKind::KeywordClass),
+    (b"struct", TokenKind::KeywordStruct),
+    (b"fn", TokenKind::KeywordFn),
+    (b"let", TokenKind::KeywordLet),
+    (b"var", TokenKind::KeywordVar),
+    (b"if", TokenKind::KeywordIf),
+    (b"else", TokenKind::KeywordElse),
+    (b"while", TokenKind::KeywordWhile),
+    (b"for", TokenKind::KeywordFor),
+    (b"in", TokenKind::KeywordIn),
+    (b"return", TokenKind::KeywordReturn),
+    (b"break", TokenKind::KeywordBreak),
+    (b"continue", TokenKind::KeywordContinue),
+    (b"import", TokenKind::KeywordImport),
+    (b"match", TokenKind::KeywordMatch),
+    (b"pub", TokenKind::KeywordPub),
+    (b"true", TokenKind::KeywordTrue),
+    (b"false", TokenKind::KeywordFalse),
+    (b"null", TokenKind::KeywordNull),
+];
+
+// Contextual keywords are lexed as identifiers and only have a special meaning
+// in specific positions in the grammar.
+const CONTEXTUAL_KEYWORDS: &[(&[u8], ContextualKeyword)] = &[
+    (b"from", ContextualKeyword::From),
+    (b"as", ContextualKeyword::As),
+    (b"where", ContextualKeyword::Where),
+];
+
+// Bit masks of the `PUNCTUATION` and `KEYWORDS` entries starting with each
+// byte, so lookups only need to compare against the few possible candidates.
+const fn first_byte_masks(table: &[(&[u8], TokenKind)]) -> [u64; 256] {
+    assert!(table.len() <= 64);
+    let mut masks = [0u64; 256];
+    let mut i = 0;
+    while i < table.len() {
+        masks[table[i].0[0] as usize] |= 1 << i;
+        i += 1;
+    }
+    masks
+}
+
+const PUNCTUATION_MASKS: [u64; 256] = first_byte_masks(PUNCTUATION);
+const KEYWORD_MASKS: [u64; 256] = first_byte_masks(KEYWORDS);
+
+fn find_in_table(table: &[(&[u8], TokenKind)], masks: &[u64; 256], text: &[u8], exact: bool) -> Option<(usize, TokenKind)> {
+    let mut mask = masks[*text.first()? as usize];
+    while mask != 0 {
+        let (spelling, kind) = table[mask.trailing_zeros() as usize];
+        mask &= mask - 1;
+        if (exact && text == spelling) || (!exact && text.starts_with(spelling)) {
+            return Some((spelling.len(), kind));
+        }
+    }
+    None
+}
+
+pub fn token_spelling(tok: TokenKind) -> Option<&'static [u8]> {
+    PUNCTUATION
+        .iter()
+        .chain(KEYWORDS)
+        .find(|(_, kind)| *kind == tok)
+        .map(|(spelling, _)| *spelling)
+}
+
+pub fn contextual_keyword_spelling(keyword: ContextualKeyword) -> &'static [u8] {
+    CONTEXTUAL_KEYWORDS.iter().find(|(_, kw)| *kw == keyword).map(|(spelling, _)| *spelling).unwrap()
+}
+
+impl std::fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(spelling) = token_spelling(*self) {
+            return write!(f, "`{}`", String::from_utf8_lossy(spelling));
+        }
+        let name = match self {
+            TokenKind::Error => "invalid token",
+            TokenKind::Ident => "identifier",
+            TokenKind::Integer => "integer literal",
+            TokenKind::Float => "float literal",
+            TokenKind::String => "string literal",
+            TokenKind::Char => "character literal",
+            TokenKind::Newline => "newline",
+            TokenKind::DocComment => "doc comment",
+            TokenKind::InnerDocComment => "inner doc comment",
+            TokenKind::End => "end of file",
+            // Kinds without a spelling or a name here still print something useful.
+            _ => return write!(f, "{:?}", self),
+        };
+        f.write_str(name)
+    }
 }
 
 impl<'a> Tokenizer<'a> {
@@ -34,6 +418,22 @@ impl<'a> Tokenizer<'a> {
             pos: 0,
             file,
             errors,
+            value: TokenValue::None,
+            lossless: false,
+            done: false,
+            confusables: None,
+            scalar: false,
+        }
+    }
+
+    fn skip_block_comment(&mut self, begin: usize) -> usize {
+        match block_comment_end(self.source, begin) {
+            Some(end) => end,
+            None => {
+                let loc = SourceSpan::new(self.file, begin, begin + 2);
+                error!(self, &loc, "unterminated block comment");
+                self.source.len()
+            }
         }
     }
 
@@ -41,18 +441,25 @@ impl<'a> Tokenizer<'a> {
         let src = self.source;
         let mut pos = self.pos;
 
+        if pos == 0 && src.starts_with(BYTE_ORDER_MARK) {
+            pos = BYTE_ORDER_MARK.len();
+        }
+
         loop {
-            while pos < src.len() && is_whitespace(src[pos]) {
-                pos += 1;
-            }
+            pos = self.skip_whitespace_run(pos);
 
-            if pos + 2 <= src.len() && src[pos] == b'/' && src[pos + 1] == b'/' {
-                if let Some(p) = memchr::memchr(b'\n', &src[pos..]) {
-                    pos = p + 1;
-                } else {
-                    pos = src.len();
+            if byte_at(src, pos) == b'/' {
+                match byte_at(src, pos + 1) {
+                    b'/' if !self.is_doc_comment(pos) => {
+                        pos = line_end(src, pos);
+                        continue;
+                    }
+                    b'*' => {
+                        pos = self.skip_block_comment(pos);
+                        continue;
+                    }
+                    _ => {}
                 }
-                continue;
             }
 
             break;
@@ -61,96 +468,467 @@ impl<'a> Tokenizer<'a> {
         self.pos = pos;
     }
 
-    fn finish_ident(&mut self) -> (usize, TokenKind) {
+    fn skip_whitespace_run(&self, mut pos: usize) -> usize {
+        let src = self.source;
+        // Single separating spaces are the common case and are cheaper to
+        // check directly than classifying a whole word.
+        if !self.scalar && is_whitespace(byte_at(src, pos)) && is_whitespace(byte_at(src, pos + 1)) {
+            while let Some(word) = swar_load(src, pos) {
+                if word == SWAR_ONES * b' ' as u64 {
+                    pos += 8;
+                    continue;
+                }
+                let len = swar_prefix_len(swar_whitespace(word));
+                pos += len;
+                if len < 8 {
+                    return pos;
+                }
+            }
+        }
+
+        while pos < src.len() && is_whitespace(src[pos]) {
+            pos += 1;
+        }
+        pos
+    }
+
+    fn is_doc_comment(&self, pos: usize) -> bool {
+        is_doc_comment(self.source, pos)
+    }
+
+    fn finish_doc_comment(&mut self) -> (usize, TokenKind) {
         let src = self.source;
         let begin = self.pos;
+        let end = line_end(src, begin);
+
+        let kind = match src[begin + 2] {
+            b'!' => TokenKind::InnerDocComment,
+            _ => TokenKind::DocComment,
+        };
+        (end - begin, kind)
+    }
+
+    fn skip_ident(&self, begin: usize) -> (usize, bool) {
+        let src = self.source;
         let mut pos = begin;
+        let mut ascii = true;
+        if !self.scalar {
+            // One table lookup per byte is cheaper than both the range checks
+            // and classifying whole words, as identifiers rarely span many words.
+            while pos < src.len() && IDENT_CONTINUE[src[pos] as usize] {
+                pos += 1;
+            }
+        }
         loop {
-            let cur = if pos < src.len() { src[pos] } else { b'\0' };
-            if matches!(cur, b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_') {
+            let ch = byte_at(src, pos);
+            if is_ident_continue(ch) {
                 pos += 1;
+            } else if let Some(len) = (ch >= 0x80).then(|| is_unicode_ident_continue(src, pos)).flatten() {
+                ascii = false;
+                pos += len;
             } else {
                 break;
             }
         }
+        (pos, ascii)
+    }
 
-        let kind = match &src[begin..pos] {
-            b"class" => TokenKind::KeywordClass,
-            b"struct" => TokenKind::KeywordStruct,
-            _ => TokenKind::Ident,
+    fn finish_ident(&mut self) -> (usize, TokenKind) {
+        let src = self.source;
+        let begin = self.pos;
+        let (pos, ascii) = self.skip_ident(begin);
+        let text = &src[begin..pos];
+
+        let kind = match find_in_table(KEYWORDS, &KEYWORD_MASKS, text, true) {
+            Some((_, kind)) => kind,
+            None => TokenKind::Ident,
         };
+        if let Some((_, keyword)) = CONTEXTUAL_KEYWORDS.iter().find(|(spelling, _)| *spelling == text) {
+            self.value = TokenValue::Contextual(*keyword);
+        }
+
+        if !ascii || self.confusables.is_some() {
+            self.check_confusable(begin, pos, ascii);
+        }
 
         (pos - begin, kind)
     }
 
+    fn is_raw_ident(&self) -> bool {
+        let src = self.source;
+        let pos = self.pos;
+        byte_at(src, pos + 1) == b'#'
+            && (matches!(byte_at(src, pos + 2), b'a'..=b'z' | b'A'..=b'Z' | b'_')
+                || is_unicode_ident_start(src, pos + 2).is_some())
+    }
+
+    fn finish_raw_ident(&mut self) -> (usize, TokenKind) {
+        let begin = self.pos;
+        let (pos, ascii) = self.skip_ident(begin + 2);
+
+        if !ascii || self.confusables.is_some() {
+            self.check_confusable(begin, pos, ascii);
+        }
+
+        (pos - begin, TokenKind::Ident)
+    }
+
+    fn check_confusable(&mut self, begin: usize, end: usize, ascii: bool) {
+        let span = SourceSpan::new(self.file, begin, end);
+        let name = ident_name(&self.source[begin..end]).into_owned();
+        if !ascii && !name.as_str().is_single_script() {
+            warning!(self, &span, "identifier `{}` mixes characters from multiple scripts", name);
+        }
+
+        let confusables = match &mut self.confusables {
+            Some(confusables) => confusables,
+            None => {
+                // Identifiers are only compared once the first non-ASCII one
+                // shows up, so collect the (ASCII) ones seen before it.
+                let mut confusables: HashMap<String, Vec<String>> = HashMap::new();
+                let prefix = Tokenizer::new(&NullErrors, self.file, &self.source[..begin]);
+                for token in prefix.filter(|t| t.kind == TokenKind::Ident) {
+                    let name = ident_name(&self.source[token.span.begin()..token.span.end()]);
+                    let names = confusables.entry(skeleton(&name).collect()).or_default();
+                    if !names.iter().any(|n| *n == name) {
+                        names.push(name.into_owned());
+                    }
+                }
+                self.confusables.insert(confusables)
+            }
+        };
+
+        let names = confusables.entry(skeleton(&name).collect()).or_default();
+        if names.contains(&name) {
+            return;
+        }
+        if let Some(other) = names.first() {
+            let other = other.clone();
+            names.push(name.clone());
+            warning!(self, &span, "identifier `{}` is confusable with `{}`", name, other);
+        } else {
+            names.push(name);
+        }
+    }
+
+    fn skip_digits(&self, mut pos: usize, radix: u32) -> usize {
+        let src = self.source;
+        while pos < src.len() && (src[pos] == b'_' || (src[pos] as char).is_digit(radix)) {
+            pos += 1;
+        }
+        pos
+    }
+
+    fn finish_radix_number(&mut self, radix: u32, name: &str) -> (usize, TokenKind) {
+        let src = self.source;
+        let begin = self.pos;
+        let digits_begin = begin + 2;
+        let digits_end = self.skip_digits(digits_begin, radix);
+
+        let mut pos = digits_end;
+        while is_ident_continue(byte_at(src, pos)) {
+            pos += 1;
+        }
+
+        let span = SourceSpan::new(self.file, begin, pos);
+        let mut digits = src[digits_begin..digits_end].iter().filter(|c| **c != b'_');
+        let bad = byte_at(src, digits_end);
+        if digits_end < pos && is_digit(bad) {
+            let loc = SourceSpan::new(self.file, digits_end, digits_end + 1);
+            error!(self, &loc, "invalid digit '{}' in {} literal", bad as char, name);
+        } else if digits.clone().next().is_none() {
+            error!(self, &span, "missing digits in {} literal", name);
+        } else if digits_end < pos {
+            let loc = SourceSpan::new(self.file, digits_end, pos);
+            let suffix = String::from_utf8_lossy(&src[digits_end..pos]);
+            error!(self, &loc, "invalid suffix `{}` on {} literal", suffix, name);
+        } else {
+            let value = digits.try_fold(0u64, |value, ch| {
+                let digit = (*ch as char).to_digit(radix).unwrap() as u64;
+                value.checked_mul(radix as u64)?.checked_add(digit)
+            });
+            match value {
+                Some(value) => self.value = TokenValue::Integer(value),
+                None => error!(self, &span, "integer literal is too large"),
+            }
+        }
+
+        (pos - begin, TokenKind::Integer)
+    }
+
     fn finish_number(&mut self) -> (usize, TokenKind) {
-        todo!()
+        let src = self.source;
+        let begin = self.pos;
+
+        match (src[begin], byte_at(src, begin + 1)) {
+            (b'0', b'x') => return self.finish_radix_number(16, "hexadecimal"),
+            (b'0', b'o') => return self.finish_radix_number(8, "octal"),
+            (b'0', b'b') => return self.finish_radix_number(2, "binary"),
+            _ => {}
+        }
+
+        let mut kind = TokenKind::Integer;
+        let mut valid = true;
+        let mut pos = self.skip_digits(begin, 10);
+
+        if byte_at(src, pos) == b'.' && is_digit(byte_at(src, pos + 1)) {
+            kind = TokenKind::Float;
+            pos = self.skip_digits(pos + 1, 10);
+        }
+
+        if matches!(byte_at(src, pos), b'e' | b'E') {
+            kind = TokenKind::Float;
+            let exp_begin = pos;
+            pos += 1;
+            if matches!(byte_at(src, pos), b'+' | b'-') {
+                pos += 1;
+            }
+            if is_digit(byte_at(src, pos)) {
+                pos = self.skip_digits(pos, 10);
+            } else {
+                let loc = SourceSpan::new(self.file, exp_begin, pos);
+                error!(self, &loc, "missing digits in float exponent");
+                valid = false;
+            }
+        }
+
+        if byte_at(src, pos) == b'.' && is_digit(byte_at(src, pos + 1)) {
+            let extra_begin = pos;
+            while matches!(byte_at(src, pos), b'.' | b'0'..=b'9' | b'_') {
+                pos += 1;
+            }
+            let loc = SourceSpan::new(self.file, extra_begin, pos);
+            error!(self, &loc, "unexpected `.` in number literal");
+            valid = false;
+        }
+
+        let name = if kind == TokenKind::Float { "float" } else { "integer" };
+        let suffix_begin = pos;
+        while is_ident_continue(byte_at(src, pos)) {
+            pos += 1;
+        }
+        if suffix_begin < pos && valid {
+            let loc = SourceSpan::new(self.file, suffix_begin, pos);
+            let suffix = String::from_utf8_lossy(&src[suffix_begin..pos]);
+            error!(self, &loc, "invalid suffix `{}` on {} literal", suffix, name);
+            valid = false;
+        }
+
+        if valid {
+            let span = SourceSpan::new(self.file, begin, pos);
+            let text: String = src[begin..pos].iter().filter(|c| **c != b'_').map(|c| *c as char).collect();
+            if kind == TokenKind::Float {
+                match text.parse::<f64>() {
+                    Ok(value) if value.is_finite() => self.value = TokenValue::Float(value),
+                    _ => error!(self, &span, "float literal is out of range"),
+                }
+            } else {
+                match text.parse::<u64>() {
+                    Ok(value) => self.value = TokenValue::Integer(value),
+                    Err(_) => error!(self, &span, "integer literal is too large"),
+                }
+            }
+        }
+
+        (pos - begin, kind)
+    }
+
+    fn finish_string(&mut self) -> (usize, TokenKind) {
+        let src = self.source;
+        let begin = self.pos;
+        let mut pos = begin + 1;
+
+        loop {
+            let Some(p) = memchr::memchr2(b'"', b'\\', &src[pos..]) else {
+                let loc = SourceSpan::new(self.file, begin, begin + 1);
+                error!(self, &loc, "unterminated string literal");
+                pos = src.len();
+                break;
+            };
+            pos += p;
+
+            if src[pos] == b'"' {
+                pos += 1;
+                break;
+            }
+
+            let (end, result) = read_escape(src, pos);
+            if let Err(message) = result {
+                let loc = SourceSpan::new(self.file, pos, end);
+                error!(self, &loc, "{}", message);
+            }
+            pos = end;
+        }
+
+        (pos - begin, TokenKind::String)
+    }
+
+    fn finish_raw_string(&mut self) -> (usize, TokenKind) {
+        let src = self.source;
+        let begin = self.pos;
+        let hashes = raw_string_hashes(&src[begin..]);
+        let mut pos = begin + hashes + 2;
+
+        loop {
+            let Some(p) = memchr::memchr(b'"', &src[pos..]) else {
+                let loc = SourceSpan::new(self.file, begin, begin + hashes + 2);
+                error!(self, &loc, "unterminated raw string literal");
+                pos = src.len();
+                break;
+            };
+            pos += p + 1;
+
+            let closing = src.get(pos..pos + hashes).unwrap_or_default();
+            if closing.len() == hashes && closing.iter().all(|c| *c == b'#') {
+                pos += hashes;
+                break;
+            }
+        }
+
+        (pos - begin, TokenKind::String)
+    }
+
+    fn is_raw_string(&self) -> bool {
+        let src = &self.source[self.pos..];
+        byte_at(src, raw_string_hashes(src) + 1) == b'"'
+    }
+
+    fn finish_char(&mut self) -> (usize, TokenKind) {
+        let src = self.source;
+        let begin = self.pos;
+        let mut pos = begin + 1;
+        let mut value = None;
+        let mut count = 0;
+        let mut valid = true;
+
+        loop {
+            let ch = byte_at(src, pos);
+            if pos >= src.len() || ch == b'\n' || ch == b'\r' {
+                let loc = SourceSpan::new(self.file, begin, begin + 1);
+                error!(self, &loc, "unterminated character literal");
+                return (pos - begin, TokenKind::Char);
+            }
+
+            match ch {
+                b'\'' => {
+                    pos += 1;
+                    break;
+                }
+                b'\\' => {
+                    let (end, result) = read_escape(src, pos);
+                    match result {
+                        Ok(ch) => value = Some(ch),
+                        Err(message) => {
+                            let loc = SourceSpan::new(self.file, pos, end);
+                            error!(self, &loc, "{}", message);
+                            valid = false;
+                        }
+                    }
+                    pos = end;
+                }
+                _ => {
+                    value = decode_char(src, pos);
+                    pos += char_len(src, pos);
+                }
+            }
+            count += 1;
+        }
+
+        let span = SourceSpan::new(self.file, begin, pos);
+        if count == 0 {
+            error!(self, &span, "empty character literal");
+        } else if count > 1 {
+            error!(self, &span, "character literal may only contain one character");
+        } else if let (true, Some(ch)) = (valid, value) {
+            self.value = TokenValue::Char(ch);
+        }
+
+        (pos - begin, TokenKind::Char)
     }
 
     fn finish_newline(&mut self) -> (usize, TokenKind) {
-        (1, TokenKind::Newline)
+        let src = self.source;
+        match (src[self.pos], byte_at(src, self.pos + 1)) {
+            (b'\r', b'\n') => (2, TokenKind::Newline),
+            _ => (1, TokenKind::Newline),
+        }
     }
 
     fn read_token(&mut self) -> Option<(usize, TokenKind)> {
         let src = self.source;
         let pos = self.pos;
 
-        let cur = if pos < src.len() { src[pos] } else { b'\0' };
-        let next = if pos + 1 < src.len() { src[pos + 1] } else { b'\0' };
+        let cur = byte_at(src, pos);
+        let next = byte_at(src, pos + 1);
         let (len, kind) = match cur {
+            b'r' if self.is_raw_string() => self.finish_raw_string(),
+            b'r' if self.is_raw_ident() => self.finish_raw_ident(),
             b'a'..=b'z' | b'A'..=b'Z' | b'_' => self.finish_ident(),
+            0x80.. if is_unicode_ident_start(src, pos).is_some() => self.finish_ident(),
             b'0'..=b'9' => self.finish_number(),
-            b'\n' => self.finish_newline(),
-            b'=' => match next {
-                b'=' => (2, TokenKind::Equals),
-                _ => (1, TokenKind::Assign),
-            },
-            b'>' => match next {
-                b'=' => (2, TokenKind::GreaterEquals),
-                _ => (1, TokenKind::Greater),
-            },
-            b'<' => match next {
-                b'=' => (2, TokenKind::LessEquals),
-                _ => (1, TokenKind::Less),
-            },
-            b'{' => (1, TokenKind::BraceOpen),
-            b'}' => (1, TokenKind::BraceClose),
-            b'+' => (1, TokenKind::Add),
-            _ => return None,
+            b'\n' | b'\r' => self.finish_newline(),
+            b'"' => self.finish_string(),
+            b'/' if next == b'/' => self.finish_doc_comment(),
+            b'\'' => self.finish_char(),
+            _ => find_in_table(PUNCTUATION, &PUNCTUATION_MASKS, &src[pos..], false)?,
         };
 
         Some((len, kind))
     }
 
+    fn starts_token(&self, pos: usize) -> bool {
+        let ch = byte_at(self.source, pos);
+        matches!(ch, b'a'..=b'z' | b'A'..=b'Z' | b'_' | b'0'..=b'9' | b'\n' | b'\r' | b'"' | b'\'')
+            || PUNCTUATION_MASKS[ch as usize] != 0
+            || (ch >= 0x80 && is_unicode_ident_start(self.source, pos).is_some())
+    }
+
     fn bad_token(&mut self) -> (usize, TokenKind) {
-        let loc = SourceSpan::new(self.file, self.pos, self.pos + 1);
         let ch = *self.source.get(self.pos).expect("should have returned End");
-        let ch_str = match ch {
-            0x20..0x7e => format!("'{}'", ch as char),
-            _ => format!("(byte 0x{:02x})", ch),
+        let (ch_str, len) = match (ch, decode_char(self.source, self.pos)) {
+            (0x20..0x7e, _) => (Some(format!("'{}'", ch as char)), 1),
+            (0x80.., Some(c)) => (Some(format!("'{}' (U+{:04X})", c, c as u32)), c.len_utf8()),
+            // Invalid UTF-8 has already been reported once for the whole file.
+            (0x80.., None) => (None, 1),
+            _ => (Some(format!("(byte 0x{:02x})", ch)), 1),
         };
-        error!(self, &loc, "unrecognized token: {}", ch_str);
+        if let Some(ch_str) = ch_str {
+            let loc = SourceSpan::new(self.file, self.pos, self.pos + len);
+            error!(self, &loc, "unrecognized token: {}", ch_str);
+        }
 
         let begin = self.pos;
-        loop {
+        self.pos += len;
+        while self.pos < self.source.len() && !is_whitespace(self.source[self.pos]) && !self.starts_token(self.pos) {
             self.pos += 1;
-            let ch = self.source.get(self.pos).map(|c| *c).unwrap_or(b'\0');
-            if is_whitespace(ch) || self.read_token().is_some() {
-                break;
-            }
         }
 
         (self.pos - begin, TokenKind::Error)
     }
 
+    fn check_utf8(&self, begin: usize, end: usize) {
+        check_utf8_range(self.errors, self.file, self.source, begin, end);
+    }
+
     fn scan(&mut self) -> Option<Token> {
+        let leading_begin = self.pos;
         self.skip_whitespace();
 
+        let begin = self.pos;
+        let leading = if self.lossless { begin - leading_begin } else { 0 };
+
         if self.pos >= self.source.len() {
+            if self.lossless && !self.done {
+                self.done = true;
+                let mut token = Token::end();
+                token.span = SourceSpan::new(self.file, begin, begin);
+                token.trivia.leading = leading as u32;
+                return Some(token);
+            }
             return None;
         }
 
-        let begin = self.pos;
+        self.value = TokenValue::None;
         let (len, kind) = match self.read_token() {
             Some(pair) => pair,
             None => self.bad_token(),
@@ -161,13 +939,24 @@ impl<'a> Tokenizer<'a> {
 
         self.pos = end;
 
-        Some(Token { kind, span })
+        let mut trailing = 0;
+        if self.lossless && kind != TokenKind::Newline {
+            self.skip_whitespace();
+            trailing = self.pos - end;
+        }
+
+        let value = self.value;
+        let trivia = Trivia {
+            leading: leading as u32,
+            trailing: trailing as u32,
+        };
+        Some(Token { kind, span, value, trivia })
     }
 }
 
-impl<'a> Errors for Tokenizer<'a> {
-    fn push(&self, int_loc: &InternalLocation, loc: &dyn Locatable, message: String, context: Vec<String>) {
-        self.errors.push(int_loc, loc, message, context);
+impl<'a> ForwardErrors for Tokenizer<'a> {
+    fn errors(&self) -> &dyn Errors {
+        self.errors
     }
 }
 
@@ -179,6 +968,140 @@ impl<'a> Iterator for Tokenizer<'a> {
     }
 }
 
+fn check_utf8_range(errors: &dyn Errors, file: SourceFile, source: &[u8], begin: usize, end: usize) {
+    if let Err(err) = std::str::from_utf8(&source[begin..end]) {
+        let pos = begin + err.valid_up_to();
+        let len = err.error_len().unwrap_or(end - pos);
+        let loc = SourceSpan::new(file, pos, pos + len);
+        error!(errors, &loc, "invalid UTF-8 in source file at byte offset {}", pos);
+    }
+}
+
 pub fn tokenize<'a>(errors: &'a dyn Errors, file: SourceFile, source: &'a [u8]) -> Tokenizer<'a> {
-    Tokenizer::new(errors, file, source)
+    let tokenizer = Tokenizer::new(errors, file, source);
+    tokenizer.check_utf8(0, source.len());
+    tokenizer
+}
+
+pub struct TextEdit<'a> {
+    pub range: std::ops::Range<usize>,
+    pub text: &'a [u8],
+}
+
+impl TextEdit<'_> {
+    pub fn apply(&self, source: &[u8]) -> Vec<u8> {
+        [&source[..self.range.start], self.text, &source[self.range.end..]].concat()
+    }
+}
+
+// Number of bytes the tokenizer may inspect past the end of a token (and its
+// trailing trivia) when deciding where it ends. The only unbounded lookahead is
+// the run of `#` after the `r` of a raw string, see `relex_boundary`.
+const RELEX_LOOKAHEAD: usize = 4;
+
+// The earliest position whose tokens may change when the source is edited at
+// `pos`: an edit inside or right after `r###` can turn it into a raw string.
+fn relex_boundary(source: &[u8], pos: usize) -> usize {
+    let before = &source[..pos.min(source.len())];
+    let hashes = before.iter().rev().take_while(|c| **c == b'#').count();
+    match before.len().checked_sub(hashes + 1) {
+        Some(r) if before[r] == b'r' => r,
+        _ => pos,
+    }
+}
+
+fn token_extent(token: &Token) -> std::ops::Range<usize> {
+    token.leading_trivia().begin()..token.trailing_trivia().end()
+}
+
+fn shift_token(token: &Token, delta: isize) -> Token {
+    let span = token.span;
+    let begin = (span.begin() as isize + delta) as usize;
+    let end = (span.end() as isize + delta) as usize;
+    Token {
+        span: SourceSpan::new(span.file(), begin, end),
+        ..token.clone()
+    }
+}
+
+/// Update `tokens` of a file after `edit` has been applied to it, resulting in
+/// `source`. Only the tokens around the edit are re-lexed, the rest are reused
+/// with shifted spans, matching the result of tokenizing `source` from scratch.
+/// `tokens` may come from either `tokenize` or `tokenize_lossless`. Returns the
+/// range of re-lexed tokens in the updated vector.
+pub fn relex(
+    errors: &dyn Errors,
+    file: SourceFile,
+    source: &[u8],
+    tokens: &mut Vec<Token>,
+    edit: &TextEdit,
+) -> std::ops::Range<usize> {
+    let lossless = tokens.last().is_some_and(|t| t.kind == TokenKind::End);
+    let delta = edit.text.len() as isize - edit.range.len() as isize;
+
+    // Bytes before the edit are the same in the old and the new source.
+    let boundary = relex_boundary(source, edit.range.start);
+    let keep = tokens.partition_point(|t| token_extent(t).end + RELEX_LOOKAHEAD <= boundary);
+    let restart = match keep {
+        0 => 0,
+        n => token_extent(&tokens[n - 1]).end,
+    };
+
+    let mut tokenizer = Tokenizer::new(errors, file, source);
+    tokenizer.pos = restart;
+    tokenizer.lossless = lossless;
+
+    let mut old = keep;
+    let mut relexed = Vec::new();
+    let mut resync = None;
+    for token in tokenizer {
+        let begin = token_extent(&token).start as isize - delta;
+        while old < tokens.len() && (token_extent(&tokens[old]).start as isize) < begin {
+            old += 1;
+        }
+
+        if let Some(prev) = tokens.get(old)
+            && token_extent(prev).start >= edit.range.end
+            && shift_token(prev, delta) == token
+        {
+            resync = Some(old);
+            break;
+        }
+        relexed.push(token);
+    }
+
+    let tail = match resync {
+        Some(index) => tokens[index..].iter().map(|t| shift_token(t, delta)).collect(),
+        None => Vec::new(),
+    };
+
+    let window_end = relexed.last().map_or(restart, |t| token_extent(t).end);
+    let window_end = window_end.max((edit.range.start as isize + edit.text.len() as isize) as usize);
+    check_utf8_range(errors, file, source, restart, window_end.min(source.len()));
+
+    let relexed_len = relexed.len();
+    tokens.truncate(keep);
+    tokens.extend(relexed);
+    tokens.extend(tail);
+    keep..keep + relexed_len
+}
+
+/// Tokenize without the fast paths, used as a baseline for benchmarks.
+#[cfg(feature = "bench")]
+pub fn tokenize_scalar<'a>(errors: &'a dyn Errors, file: SourceFile, source: &'a [u8]) -> Tokenizer<'a> {
+    let mut tokenizer = Tokenizer::new(errors, file, source);
+    tokenizer.check_utf8(0, source.len());
+    tokenizer.scalar = true;
+    tokenizer
+}
+
+/// Tokenize keeping all whitespace and comments as `Token::trivia`: trivia
+/// following a newline (or at the start of the file) leads the next token, any
+/// other trivia trails the previous one. The stream is terminated by an `End`
+/// token so that trivia at the end of the file is not lost.
+pub fn tokenize_lossless<'a>(errors: &'a dyn Errors, file: SourceFile, source: &'a [u8]) -> Tokenizer<'a> {
+    let mut tokenizer = Tokenizer::new(errors, file, source);
+    tokenizer.check_utf8(0, source.len());
+    tokenizer.lossless = true;
+    tokenizer
 }
@@ -20,8 +20,10 @@ macro_rules! index_type {
 pub mod compiler;
 use compiler::*;
 
-pub use compiler::{Compiler, SourceSpan, SourceFile};
+pub use compiler::{Compiler, NullErrors, SourceSpan, SourceFile};
 
 pub mod ast;
 pub mod front;
-mod dump;
+pub mod modules;
+pub mod sema;
+pub mod dump;
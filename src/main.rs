@@ -1,18 +1,45 @@
 use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
 
-use ebi::{front, Compiler};
+use std::path::Path;
+
+use ebi::{dump::{dump, Indent}, modules::ModuleResolver, sema, Compiler};
 
 const COL_RED: &str = "\x1b[91m";
+const COL_YELLOW: &str = "\x1b[93m";
 const COL_GRAY: &str = "\x1b[90m";
 const COL_RESET: &str = "\x1b[0m";
 
 fn main_safe(compiler: &Compiler) {
-    let path = std::env::args().nth(1).expect("expected file name");
-    let file = compiler.load_file(&path);
+    let mut resolver = ModuleResolver::new(compiler);
+    let mut path = None;
+    let mut indent = Indent::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--root" => resolver.add_root(args.next().expect("expected a directory after --root")),
+            "--indent" => {
+                let width = args.next().and_then(|n| n.parse().ok()).expect("expected a number after --indent");
+                indent = Indent::Spaces(width);
+            }
+            "--tabs" => indent = Indent::Tabs,
+            _ => path = Some(arg),
+        }
+    }
+    let path = path.expect("expected file name");
 
-    let tokens = front::tokenize(compiler, file.file(), file.data());
-    let root = front::parse(compiler, tokens);
-    println!("{:#?}", root.root());
+    // Without explicit roots, imports are resolved next to the main file.
+    if resolver.roots().is_empty() {
+        resolver.add_root(Path::new(&path).parent().unwrap_or(Path::new("")));
+    }
+
+    let main = resolver.load(&path);
+    resolver.check_references();
+    for module in resolver.modules() {
+        let nodes = compiler.add_nodes(module.root());
+        sema::check(compiler, module.file().data(), &nodes);
+    }
+    let main = resolver.module(main);
+    print!("{}", dump(main.file().data(), main.root(), indent));
 }
 
 fn main() {
@@ -22,8 +49,9 @@ fn main() {
         main_safe(&compiler);
     }));
 
-    let errors = compiler.errors();
-    for error in errors {
+    let warnings = compiler.warnings().into_iter().map(|w| ("Warning:", COL_YELLOW, w));
+    let errors = compiler.errors().into_iter().map(|e| ("Error:", COL_RED, e));
+    for (kind, color, error) in warnings.chain(errors) {
         let span_info = compiler.span_info(error.location);
 
         let loc = match span_info {
@@ -42,7 +70,7 @@ fn main() {
         let int_path = error.internal_location.file.replace('\\', "/");
         let int_line = error.internal_location.line;
 
-        println!("{COL_RED}Error:{COL_RESET} {loc} {message} {COL_GRAY}({int_path}:{int_line}){COL_RESET}");
+        println!("{color}{kind}{COL_RESET} {loc} {message} {COL_GRAY}({int_path}:{int_line}){COL_RESET}");
     }
 
     if let Err(panic) = result {
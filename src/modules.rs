@@ -0,0 +1,425 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    ast::*,
+    compiler::SourceFileInfo,
+    front::{self, ident_name, string_value},
+    *,
+};
+
+index_type!(ModuleId);
+
+const EXTENSION: &str = "ebi";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    Class,
+    Struct,
+    Fn,
+    Module(ModuleId),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Symbol {
+    pub kind: SymbolKind,
+    pub public: bool,
+    pub span: SourceSpan,
+}
+
+pub struct Module {
+    id: ModuleId,
+    name: String,
+    file: Arc<SourceFileInfo>,
+    root: AstRoot,
+    namespace: HashMap<String, Symbol>,
+}
+
+impl Module {
+    pub fn id(&self) -> ModuleId {
+        self.id
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn file(&self) -> &SourceFileInfo {
+        &self.file
+    }
+    pub fn root(&self) -> &AstRoot {
+        &self.root
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.namespace.get(name)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Loading,
+    Done,
+}
+
+// An import whose target has been located on disk but not loaded yet.
+struct PendingImport {
+    binding: String,
+    token: Token,
+    name: String,
+    path: PathBuf,
+}
+
+/// Loads a program spread over several files, following `import` declarations
+/// relative to a list of source roots.
+pub struct ModuleResolver<'c> {
+    compiler: &'c Compiler,
+    roots: Vec<PathBuf>,
+    modules: Vec<Module>,
+    states: Vec<State>,
+    by_path: HashMap<PathBuf, ModuleId>,
+    stack: Vec<ModuleId>,
+}
+
+impl<'c> ModuleResolver<'c> {
+    pub fn new(compiler: &'c Compiler) -> ModuleResolver<'c> {
+        ModuleResolver {
+            compiler,
+            roots: Vec::new(),
+            modules: Vec::new(),
+            states: Vec::new(),
+            by_path: HashMap::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    pub fn add_root(&mut self, root: impl Into<PathBuf>) {
+        self.roots.push(root.into());
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    pub fn modules(&self) -> &[Module] {
+        &self.modules
+    }
+
+    pub fn module(&self, id: ModuleId) -> &Module {
+        &self.modules[id.index()]
+    }
+
+    /// Loads `path` and everything it imports, returning the module for `path`.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> ModuleId {
+        let path = path.as_ref();
+        let name = self.module_name(path);
+        self.load_module(path.to_path_buf(), name)
+    }
+
+    fn module_name(&self, path: &Path) -> String {
+        let path = canonical(path);
+        let stem = path.with_extension("");
+        for root in &self.roots {
+            if let Ok(relative) = stem.strip_prefix(canonical(root)) {
+                let parts = relative.iter().map(|p| p.to_string_lossy()).collect::<Vec<_>>();
+                return parts.join(".");
+            }
+        }
+        stem.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+    }
+
+    fn load_module(&mut self, path: PathBuf, name: String) -> ModuleId {
+        let key = canonical(&path);
+        if let Some(&id) = self.by_path.get(&key) {
+            return id;
+        }
+
+        let file = self.compiler.load_file(&path.to_string_lossy());
+        let tokens = front::tokenize(self.compiler, file.file(), file.data());
+        let root = front::parse(self.compiler, tokens);
+
+        let id = ModuleId::new(self.modules.len());
+        self.by_path.insert(key, id);
+        self.states.push(State::Loading);
+        self.modules.push(Module {
+            id,
+            name,
+            file,
+            root,
+            namespace: HashMap::new(),
+        });
+        self.stack.push(id);
+
+        for import in self.find_imports(id, &path) {
+            let target = match self.by_path.get(&canonical(&import.path)) {
+                Some(&target) if self.states[target.index()] == State::Loading => {
+                    self.report_cycle(&import.token, target);
+                    target
+                }
+                Some(&target) => target,
+                None => self.load_module(import.path, import.name),
+            };
+            let symbol = Symbol {
+                kind: SymbolKind::Module(target),
+                public: false,
+                span: import.token.span,
+            };
+            self.define(id, import.binding, symbol);
+        }
+
+        for (name, symbol) in self.find_decls(id) {
+            self.define(id, name, symbol);
+        }
+
+        self.states[id.index()] = State::Done;
+        self.stack.pop();
+        id
+    }
+
+    fn find_imports(&self, id: ModuleId, path: &Path) -> Vec<PendingImport> {
+        let module = self.module(id);
+        let source = module.file.data();
+        let Ast::Root(_, decls) = module.root.root() else { return Vec::new() };
+
+        let mut imports = Vec::new();
+        for decl in *decls {
+            let Ast::Import(_, names, from) = decl else { continue };
+            let Some(last) = names.last() else { continue };
+            let parts = names.iter().map(|n| ident_name(&source[n.span.begin()..n.span.end()])).collect::<Vec<_>>();
+            let binding = parts[parts.len() - 1].to_string();
+
+            let target = match from {
+                Some(from) => {
+                    let text = string_value(&source[from.span.begin()..from.span.end()]);
+                    self.find_file(path, &text).map(|path| (self.module_name(&path), path)).ok_or_else(|| {
+                        format!("cannot find file `{}` imported as `{}`", text, binding)
+                    })
+                }
+                None => {
+                    let name = parts.join(".");
+                    self.find_in_roots(&parts).map(|path| (name.clone(), path)).ok_or_else(|| {
+                        let roots = self.roots.iter().map(|r| format!("`{}`", r.display())).collect::<Vec<_>>();
+                        format!("cannot find module `{}` in source roots {}", name, roots.join(", "))
+                    })
+                }
+            };
+
+            match target {
+                Ok((name, path)) => imports.push(PendingImport { binding, token: last.clone(), name, path }),
+                Err(message) => error!(self, last, "{}", message),
+            }
+        }
+        imports
+    }
+
+    fn find_in_roots(&self, parts: &[impl AsRef<str>]) -> Option<PathBuf> {
+        self.roots.iter().find_map(|root| {
+            let mut path = root.clone();
+            parts.iter().for_each(|part| path.push(part.as_ref()));
+            path.set_extension(EXTENSION);
+            path.is_file().then_some(path)
+        })
+    }
+
+    // Paths in `import x from "..."` are relative to the importing file, then to the source roots.
+    fn find_file(&self, importer: &Path, text: &str) -> Option<PathBuf> {
+        let mut relative = PathBuf::from(text);
+        if relative.extension().is_none() {
+            relative.set_extension(EXTENSION);
+        }
+
+        let dir = importer.parent().unwrap_or(Path::new(""));
+        std::iter::once(dir)
+            .chain(self.roots.iter().map(|r| r.as_path()))
+            .map(|dir| dir.join(&relative))
+            .find(|path| path.is_file())
+    }
+
+    fn find_decls(&self, id: ModuleId) -> Vec<(String, Symbol)> {
+        let module = self.module(id);
+        let source = module.file.data();
+        let Ast::Root(_, decls) = module.root.root() else { return Vec::new() };
+
+        decls
+            .iter()
+            .filter_map(|decl| {
+                let (vis, kind, name) = match decl {
+                    Ast::ClassDecl(_, vis, kw, name, ..) if kw.kind == TokenKind::KeywordStruct => (vis, SymbolKind::Struct, name),
+                    Ast::ClassDecl(_, vis, _, name, ..) => (vis, SymbolKind::Class, name),
+                    Ast::FnDecl(_, vis, _, name, ..) => (vis, SymbolKind::Fn, name),
+                    _ => return None,
+                };
+                let symbol = Symbol {
+                    kind,
+                    public: vis.is_some(),
+                    span: name.span,
+                };
+                Some((ident_name(&source[name.span.begin()..name.span.end()]).into_owned(), symbol))
+            })
+            .collect()
+    }
+
+    fn define(&mut self, id: ModuleId, name: String, symbol: Symbol) {
+        let namespace = &mut self.modules[id.index()].namespace;
+        if namespace.contains_key(&name) {
+            error!(self, &symbol.span, "`{}` is already defined in this module", name);
+            return;
+        }
+        namespace.insert(name, symbol);
+    }
+
+    fn report_cycle(&self, token: &Token, target: ModuleId) {
+        let start = self.stack.iter().position(|&id| id == target).unwrap_or(0);
+        let chain = self.stack[start..]
+            .iter()
+            .chain(std::iter::once(&target))
+            .map(|&id| format!("`{}`", self.module(id).name))
+            .collect::<Vec<_>>();
+        error!(self, token, "import cycle detected: {}", chain.join(" -> "));
+    }
+
+    /// Checks that `module::Item` and `module.item` references name public items of the imported module.
+    pub fn check_references(&self) {
+        for module in &self.modules {
+            let mut checker = ReferenceChecker {
+                resolver: self,
+                module,
+                scopes: vec![Vec::new()],
+            };
+            checker.visit_root(module.root.root());
+        }
+    }
+}
+
+// Walks a module keeping track of locals, which shadow imported modules.
+struct ReferenceChecker<'r, 'c> {
+    resolver: &'r ModuleResolver<'c>,
+    module: &'r Module,
+    scopes: Vec<Vec<String>>,
+}
+
+impl<'r, 'c> ReferenceChecker<'r, 'c> {
+    fn name(&self, token: &Token) -> String {
+        let source = self.module.file.data();
+        ident_name(&source[token.span.begin()..token.span.end()]).into_owned()
+    }
+
+    fn bind(&mut self, token: &Token) {
+        let name = self.name(token);
+        self.scopes.last_mut().expect("binding outside of a scope").push(name);
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().flatten().any(|local| local == name)
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(Vec::new());
+        f(self);
+        self.scopes.pop();
+    }
+
+    fn check_member(&self, base: &Token, member: &Token) {
+        let name = self.name(base);
+        if self.is_local(&name) {
+            return
+        }
+        let Some(Symbol { kind: SymbolKind::Module(target), .. }) = self.module.lookup(&name) else { return };
+        let target = self.resolver.module(*target);
+        let member_name = self.name(member);
+        match target.lookup(&member_name) {
+            Some(symbol) if symbol.public => {}
+            Some(_) => error!(self, member, "`{}` is private to module `{}`", member_name, target.name),
+            None => error!(self, member, "module `{}` has no item named `{}`", target.name, member_name),
+        }
+    }
+
+    // Returns whether `ast` was a `module::Item` or `module.item` reference.
+    fn check_path(&self, ast: &Ast) -> bool {
+        match ast {
+            Ast::Path(Ast::Name(base), _, member) | Ast::Member(Ast::Name(base), _, member) => {
+                self.check_member(base, member);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<'r, 'c, 'a> Visitor<'a> for ReferenceChecker<'r, 'c> {
+    fn visit_decl(&mut self, ast: &'a Ast<'a>) {
+        match ast {
+            Ast::FnDecl(..) => self.scoped(|this| walk_decl(this, ast)),
+            _ => walk_decl(self, ast),
+        }
+    }
+
+    fn visit_param(&mut self, ast: &'a Ast<'a>) {
+        if let Ast::Param(name, _) | Ast::Bind(name) = ast {
+            self.bind(name);
+        }
+        walk_param(self, ast);
+    }
+
+    fn visit_stmt(&mut self, ast: &'a Ast<'a>) {
+        match ast {
+            Ast::Block(..) => self.scoped(|this| walk_stmt(this, ast)),
+            Ast::Let(_, name, ..) => {
+                walk_stmt(self, ast);
+                self.bind(name);
+            }
+            Ast::For(_, name, iter, body) => {
+                self.visit_expr(iter);
+                self.scoped(|this| {
+                    this.bind(name);
+                    this.visit_stmt(body);
+                });
+            }
+            _ => walk_stmt(self, ast),
+        }
+    }
+
+    fn visit_expr(&mut self, ast: &'a Ast<'a>) {
+        if self.check_path(ast) {
+            return
+        }
+        match ast {
+            Ast::Closure(..) | Ast::Block(..) => self.scoped(|this| walk_expr(this, ast)),
+            _ => walk_expr(self, ast),
+        }
+    }
+
+    fn visit_arm(&mut self, ast: &'a Ast<'a>) {
+        self.scoped(|this| walk_arm(this, ast));
+    }
+
+    fn visit_pattern(&mut self, ast: &'a Ast<'a>) {
+        match ast {
+            Ast::Bind(name) | Ast::FieldPattern(name, None) => self.bind(name),
+            _ => walk_pattern(self, ast),
+        }
+    }
+
+    fn visit_type(&mut self, ast: &'a Ast<'a>) {
+        if !self.check_path(ast) {
+            walk_type(self, ast);
+        }
+    }
+}
+
+impl<'r, 'c> ForwardErrors for ReferenceChecker<'r, 'c> {
+    fn errors(&self) -> &dyn Errors {
+        self.resolver.compiler
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl<'c> ForwardErrors for ModuleResolver<'c> {
+    fn errors(&self) -> &dyn Errors {
+        self.compiler
+    }
+}
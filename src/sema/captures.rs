@@ -0,0 +1,185 @@
+use crate::{ast::*, front::ident_name, *};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capture {
+    pub name: String,
+    pub mutable: bool,
+    pub decl: SourceSpan,
+}
+
+/// Locals from enclosing scopes that a closure refers to, in order of first use.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClosureCaptures {
+    pub span: SourceSpan,
+    pub captures: Vec<Capture>,
+}
+
+struct Binding {
+    name: String,
+    mutable: bool,
+    decl: SourceSpan,
+    // Number of closures enclosing the declaration.
+    level: usize,
+}
+
+struct Resolver<'a> {
+    errors: &'a dyn Errors,
+    source: &'a [u8],
+    nodes: &'a NodeMap<'a>,
+    scopes: Vec<Vec<Binding>>,
+    active: Vec<usize>,
+    closures: Vec<ClosureCaptures>,
+}
+
+impl<'a> Resolver<'a> {
+    fn name(&self, token: &Token) -> String {
+        ident_name(&self.source[token.span.begin()..token.span.end()]).into_owned()
+    }
+
+    fn bind(&mut self, token: &Token, mutable: bool) {
+        let binding = Binding {
+            name: self.name(token),
+            mutable,
+            decl: token.span,
+            level: self.active.len(),
+        };
+        self.scopes.last_mut().expect("binding outside of a scope").push(binding);
+    }
+
+    fn reference(&mut self, ast: &Ast, token: &Token) {
+        let name = self.name(token);
+        let Some(binding) = self.scopes.iter().rev().flat_map(|s| s.iter().rev()).find(|b| b.name == name) else {
+            return
+        };
+        if binding.level == self.active.len() {
+            return
+        }
+
+        let capture = Capture {
+            name,
+            mutable: binding.mutable,
+            decl: binding.decl,
+        };
+        let mut reported = false;
+        for &index in self.active[binding.level..].iter().rev() {
+            let closure = &mut self.closures[index];
+            if closure.captures.iter().any(|c| c.name == capture.name) {
+                continue;
+            }
+            closure.captures.push(capture.clone());
+            // Closures capture by value, copying each local when the closure is
+            // created. A captured `var` would silently stop tracking assignments
+            // made on either side afterwards, so only `let` bindings may be captured.
+            if capture.mutable && !reported {
+                error!(self, &self.nodes.node_id(ast), "closure captures `{}`, which is declared with `var`, closures may only capture `let` bindings", capture.name);
+                reported = true;
+            }
+        }
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(Vec::new());
+        f(self);
+        self.scopes.pop();
+    }
+}
+
+impl<'r, 'a> Visitor<'a> for Resolver<'r> {
+    fn visit_decl(&mut self, ast: &'a Ast<'a>) {
+        match ast {
+            Ast::FnDecl(.., signature, body) => {
+                let scopes = std::mem::take(&mut self.scopes);
+                let active = std::mem::take(&mut self.active);
+                self.scoped(|this| {
+                    this.visit_signature(signature);
+                    this.visit_stmt(body);
+                });
+                self.scopes = scopes;
+                self.active = active;
+            }
+            _ => walk_decl(self, ast),
+        }
+    }
+
+    fn visit_param(&mut self, ast: &'a Ast<'a>) {
+        if let Ast::Param(name, _) | Ast::Bind(name) = ast {
+            self.bind(name, false);
+        }
+    }
+
+    fn visit_stmt(&mut self, ast: &'a Ast<'a>) {
+        match ast {
+            Ast::Block(..) => self.scoped(|this| walk_stmt(this, ast)),
+            Ast::Let(kw, name, _, init) => {
+                init.iter().for_each(|init| self.visit_expr(init));
+                self.bind(name, kw.kind == TokenKind::KeywordVar);
+            }
+            Ast::For(_, name, iter, body) => {
+                self.visit_expr(iter);
+                self.scoped(|this| {
+                    this.bind(name, false);
+                    this.visit_stmt(body);
+                });
+            }
+            _ => walk_stmt(self, ast),
+        }
+    }
+
+    fn visit_expr(&mut self, ast: &'a Ast<'a>) {
+        match ast {
+            Ast::Closure(kw, signature, body) => {
+                let span = match (kw, signature) {
+                    (Some(kw), _) => kw.span,
+                    (None, Ast::Signature(open, ..)) => open.span,
+                    _ => SourceSpan::unknown(),
+                };
+                self.active.push(self.closures.len());
+                self.closures.push(ClosureCaptures { span, captures: Vec::new() });
+                self.scoped(|this| {
+                    this.visit_signature(signature);
+                    this.visit_expr(body);
+                });
+                self.active.pop();
+            }
+            Ast::Block(..) => self.scoped(|this| walk_expr(this, ast)),
+            Ast::Name(token) => self.reference(ast, token),
+            _ => walk_expr(self, ast),
+        }
+    }
+
+    fn visit_arm(&mut self, ast: &'a Ast<'a>) {
+        self.scoped(|this| walk_arm(this, ast));
+    }
+
+    fn visit_pattern(&mut self, ast: &'a Ast<'a>) {
+        match ast {
+            Ast::Bind(name) | Ast::FieldPattern(name, None) => self.bind(name, false),
+            // Struct names, paths and literals in patterns never refer to locals.
+            Ast::StructPattern(_, _, fields, _) => fields.iter().for_each(|field| self.visit_pattern(field)),
+            Ast::Path(..) | Ast::Unop(..) => {}
+            _ => walk_pattern(self, ast),
+        }
+    }
+
+    // Types never refer to locals.
+    fn visit_type(&mut self, _: &'a Ast<'a>) {}
+}
+
+impl<'a> ForwardErrors for Resolver<'a> {
+    fn errors(&self) -> &dyn Errors {
+        self.errors
+    }
+}
+
+pub fn closure_captures(errors: &dyn Errors, source: &[u8], nodes: &NodeMap) -> Vec<ClosureCaptures> {
+    let mut resolver = Resolver {
+        errors,
+        source,
+        nodes,
+        scopes: vec![Vec::new()],
+        active: Vec::new(),
+        closures: Vec::new(),
+    };
+    resolver.visit_root(nodes.tree().root());
+    resolver.closures
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use crate::{ast::*, front::ident_name, *};
+
+struct GenericDecl {
+    kind: &'static str,
+    arity: usize,
+}
+
+struct Checker<'a> {
+    errors: &'a dyn Errors,
+    source: &'a [u8],
+    nodes: &'a NodeMap<'a>,
+    decls: HashMap<String, GenericDecl>,
+    scopes: Vec<Vec<String>>,
+}
+
+fn plural(count: usize, word: &str) -> String {
+    match count {
+        1 => format!("1 {}", word),
+        n => format!("{} {}s", n, word),
+    }
+}
+
+impl<'a> Checker<'a> {
+    fn name(&self, token: &Token) -> String {
+        ident_name(&self.source[token.span.begin()..token.span.end()]).into_owned()
+    }
+
+    fn is_type_param(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.iter().any(|p| p == name))
+    }
+
+    fn collect(&mut self, decls: &[Ast]) {
+        for decl in decls {
+            let (kw, name, generics) = match decl {
+                Ast::ClassDecl(_, _, kw, name, generics, _, decls) => {
+                    self.collect(decls);
+                    (kw, name, generics)
+                }
+                Ast::FnDecl(_, _, kw, name, generics, _, _) => (kw, name, generics),
+                _ => continue,
+            };
+            let kind = match kw.kind {
+                TokenKind::KeywordStruct => "struct",
+                TokenKind::KeywordClass => "class",
+                _ => "function",
+            };
+            let arity = match generics {
+                Some(Ast::Generics(_, params, _)) => params.len(),
+                _ => 0,
+            };
+            self.decls.insert(self.name(name), GenericDecl { kind, arity });
+        }
+    }
+
+    fn enter(&mut self, generics: Option<&Ast>) {
+        self.scopes.push(Vec::new());
+        let Some(Ast::Generics(_, params, _)) = generics else { return };
+
+        for param in params.iter() {
+            let Ast::GenericParam(name, _) = param else { continue };
+            let text = self.name(name);
+            if self.scopes.last().unwrap().contains(&text) {
+                error!(self, &self.nodes.node_id(param), "duplicate type parameter `{}`", text);
+            }
+            self.scopes.last_mut().unwrap().push(text);
+        }
+    }
+
+    fn check_args(&self, base: &Ast, count: usize) {
+        let Ast::Name(token) = base else { return };
+        let name = self.name(token);
+        if self.is_type_param(&name) {
+            error!(self, &self.nodes.node_id(base), "type parameter `{}` does not take type arguments", name);
+            return;
+        }
+        match self.decls.get(&name) {
+            Some(decl) if decl.arity == 0 => {
+                error!(self, &self.nodes.node_id(base), "{} `{}` is not generic and does not take type arguments", decl.kind, name);
+            }
+            Some(decl) if decl.arity != count => {
+                error!(self, &self.nodes.node_id(base), "{} `{}` expects {}, found {}", decl.kind, name, plural(decl.arity, "type argument"), count);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<'a, 'r> Visitor<'r> for Checker<'a> {
+    fn visit_decl(&mut self, ast: &'r Ast<'r>) {
+        match ast {
+            Ast::ClassDecl(_, _, _, _, generics, ..) | Ast::FnDecl(_, _, _, _, generics, ..) => {
+                self.enter(*generics);
+                walk_decl(self, ast);
+                self.scopes.pop();
+            }
+            _ => walk_decl(self, ast),
+        }
+    }
+
+    fn visit_type(&mut self, ty: &'r Ast<'r>) {
+        match ty {
+            Ast::Name(token) => {
+                let name = self.name(token);
+                if self.is_type_param(&name) {
+                    return;
+                }
+                if let Some(decl) = self.decls.get(&name).filter(|d| d.arity > 0) {
+                    error!(self, &self.nodes.node_id(ty), "missing type arguments for {} `{}`, expected {}", decl.kind, name, plural(decl.arity, "type argument"));
+                }
+            }
+            Ast::Generic(base, _, args, _) => {
+                self.check_args(base, args.len());
+                args.iter().for_each(|arg| self.visit_type(arg));
+            }
+            // Qualified names are resolved elsewhere.
+            Ast::Path(..) => {}
+            _ => walk_type(self, ty),
+        }
+    }
+
+    fn visit_expr(&mut self, ast: &'r Ast<'r>) {
+        match ast {
+            Ast::Generic(base, _, args, _) => {
+                self.check_args(base, args.len());
+                args.iter().for_each(|arg| self.visit_type(arg));
+            }
+            _ => walk_expr(self, ast),
+        }
+    }
+}
+
+impl<'a> ForwardErrors for Checker<'a> {
+    fn errors(&self) -> &dyn Errors {
+        self.errors
+    }
+}
+
+pub fn check_generics(errors: &dyn Errors, source: &[u8], nodes: &NodeMap) {
+    let root = nodes.tree().root();
+    let mut checker = Checker {
+        errors,
+        source,
+        nodes,
+        decls: HashMap::new(),
+        scopes: Vec::new(),
+    };
+    if let Ast::Root(_, decls) = root {
+        checker.collect(decls);
+    }
+    checker.visit_root(root);
+}
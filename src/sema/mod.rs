@@ -0,0 +1,15 @@
+mod captures;
+mod generics;
+mod patterns;
+
+use crate::{ast::*, *};
+
+pub use captures::{Capture, ClosureCaptures};
+
+/// Runs the semantic checks over a parsed file, returning the locals captured
+/// by every closure in it. Diagnostics are reported on the ids in `nodes`.
+pub fn check(errors: &dyn Errors, source: &[u8], nodes: &NodeMap) -> Vec<ClosureCaptures> {
+    generics::check_generics(errors, source, nodes);
+    patterns::check_patterns(errors, source, nodes);
+    captures::closure_captures(errors, source, nodes)
+}
@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+
+use crate::{ast::*, front::{ident_name, string_value}, *};
+
+// Exhaustiveness and reachability of `match` arms, using the usefulness
+// algorithm from Maranget's "Warnings for pattern matching". There is no type
+// checker yet, so the set of constructors for a column is inferred from the
+// patterns in it: `true`/`false` form a complete set, tuples and structs have a
+// single constructor and everything else is treated as an open set.
+
+#[derive(Clone, Debug, PartialEq)]
+enum Ctor {
+    Bool(bool),
+    Null,
+    Tuple(usize),
+    Struct(String, usize),
+    // Inclusive range of integers or character code points.
+    Range(i128, i128),
+    Float(u64),
+    Str(String),
+    Const(String),
+}
+
+#[derive(Clone, Debug)]
+enum Pat {
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+    Or(Vec<Pat>),
+}
+
+impl Ctor {
+    fn arity(&self) -> usize {
+        match self {
+            Ctor::Tuple(n) | Ctor::Struct(_, n) => *n,
+            _ => 0,
+        }
+    }
+
+    // Whether a row headed by `self` matches every value of the constructor `other`.
+    fn covers(&self, other: &Ctor) -> bool {
+        match (self, other) {
+            (Ctor::Range(lo, hi), Ctor::Range(a, b)) => lo <= a && b <= hi,
+            _ => self == other,
+        }
+    }
+}
+
+type Row = Vec<Pat>;
+
+fn heads<'p>(pat: &'p Pat, out: &mut Vec<&'p Ctor>) {
+    match pat {
+        Pat::Wild => {}
+        Pat::Ctor(ctor, _) => out.push(ctor),
+        Pat::Or(alts) => alts.iter().for_each(|alt| heads(alt, out)),
+    }
+}
+
+fn column(matrix: &[Row]) -> Vec<&Ctor> {
+    let mut out = Vec::new();
+    for row in matrix {
+        heads(&row[0], &mut out);
+    }
+    out
+}
+
+fn complete_signature(ctors: &[&Ctor]) -> Option<Vec<Ctor>> {
+    match ctors.first()? {
+        Ctor::Bool(_) => Some(vec![Ctor::Bool(true), Ctor::Bool(false)]),
+        ctor @ (Ctor::Tuple(_) | Ctor::Struct(..)) => Some(vec![(*ctor).clone()]),
+        _ => None,
+    }
+}
+
+fn specialize_row(head: &Pat, rest: &[Pat], ctor: &Ctor, out: &mut Vec<Row>) {
+    match head {
+        Pat::Wild => {
+            let mut row = vec![Pat::Wild; ctor.arity()];
+            row.extend_from_slice(rest);
+            out.push(row);
+        }
+        Pat::Ctor(head, args) if head.covers(ctor) => {
+            let mut row = args.clone();
+            row.extend_from_slice(rest);
+            out.push(row);
+        }
+        Pat::Ctor(..) => {}
+        Pat::Or(alts) => alts.iter().for_each(|alt| specialize_row(alt, rest, ctor, out)),
+    }
+}
+
+fn specialize(matrix: &[Row], ctor: &Ctor) -> Vec<Row> {
+    let mut out = Vec::new();
+    for row in matrix {
+        specialize_row(&row[0], &row[1..], ctor, &mut out);
+    }
+    out
+}
+
+fn default_row(head: &Pat, rest: &[Pat], out: &mut Vec<Row>) {
+    match head {
+        Pat::Wild => out.push(rest.to_vec()),
+        Pat::Ctor(..) => {}
+        Pat::Or(alts) => alts.iter().for_each(|alt| default_row(alt, rest, out)),
+    }
+}
+
+fn default_matrix(matrix: &[Row]) -> Vec<Row> {
+    let mut out = Vec::new();
+    for row in matrix {
+        default_row(&row[0], &row[1..], &mut out);
+    }
+    out
+}
+
+// Split `lo..=hi` at every range boundary in the column so that each piece is
+// either fully inside or fully outside of every range in it.
+fn split_range(ctors: &[&Ctor], lo: i128, hi: i128) -> Vec<Ctor> {
+    let mut points = vec![lo];
+    for ctor in ctors {
+        if let Ctor::Range(a, b) = ctor {
+            points.extend([*a, b.saturating_add(1)].into_iter().filter(|p| lo < *p && *p <= hi));
+        }
+    }
+    points.sort_unstable();
+    points.dedup();
+
+    let ends = points.iter().skip(1).map(|p| p - 1).chain([hi]);
+    points.iter().zip(ends).map(|(a, b)| Ctor::Range(*a, b)).collect()
+}
+
+fn useful(matrix: &[Row], v: &[Pat]) -> bool {
+    let Some(head) = v.first() else {
+        return matrix.is_empty()
+    };
+    let with_head = |head: &Pat| -> Row {
+        let mut row = vec![head.clone()];
+        row.extend_from_slice(&v[1..]);
+        row
+    };
+
+    match head {
+        Pat::Or(alts) => alts.iter().any(|alt| useful(matrix, &with_head(alt))),
+        Pat::Ctor(Ctor::Range(lo, hi), _) => {
+            split_range(&column(matrix), *lo, *hi).iter().any(|piece| {
+                useful(&specialize(matrix, piece), &v[1..])
+            })
+        }
+        Pat::Ctor(ctor, args) => {
+            let mut row = args.clone();
+            row.extend_from_slice(&v[1..]);
+            useful(&specialize(matrix, ctor), &row)
+        }
+        Pat::Wild => match complete_signature(&column(matrix)) {
+            Some(ctors) => ctors.iter().any(|ctor| {
+                let mut row = vec![Pat::Wild; ctor.arity()];
+                row.extend_from_slice(&v[1..]);
+                useful(&specialize(matrix, ctor), &row)
+            }),
+            None => useful(&default_matrix(matrix), &v[1..]),
+        },
+    }
+}
+
+struct Checker<'a> {
+    errors: &'a dyn Errors,
+    source: &'a [u8],
+    nodes: &'a NodeMap<'a>,
+    classes: HashMap<String, Vec<String>>,
+}
+
+impl<'a> Checker<'a> {
+    fn text(&self, token: &Token) -> &'a [u8] {
+        &self.source[token.span.begin()..token.span.end()]
+    }
+
+    fn name(&self, token: &Token) -> String {
+        ident_name(self.text(token)).into_owned()
+    }
+
+    fn collect_class(&mut self, ast: &Ast) {
+        if let Ast::ClassDecl(_, _, _, name, _, _, decls) = ast {
+            let fields = decls
+                .iter()
+                .filter_map(|decl| match decl {
+                    Ast::Field(_, _, name, _, _) => Some(self.name(name)),
+                    _ => None,
+                })
+                .collect();
+            self.classes.insert(self.name(name), fields);
+        }
+    }
+
+    fn range_bound(&self, ast: &Ast) -> Option<i128> {
+        match ast {
+            Ast::Literal(token) => match token.value {
+                TokenValue::Integer(value) => Some(value as i128),
+                TokenValue::Char(value) => Some(value as i128),
+                _ => None,
+            },
+            Ast::Unop(_, Ast::Literal(token)) => match token.value {
+                TokenValue::Integer(value) => Some(-(value as i128)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn lower(&self, ast: &Ast) -> Pat {
+        match ast {
+            Ast::Paren(_, pattern, _) => self.lower(pattern),
+            Ast::Literal(token) => {
+                let ctor = match (token.kind, token.value) {
+                    (TokenKind::KeywordTrue, _) => Ctor::Bool(true),
+                    (TokenKind::KeywordFalse, _) => Ctor::Bool(false),
+                    (TokenKind::KeywordNull, _) => Ctor::Null,
+                    (_, TokenValue::Float(value)) => Ctor::Float(value.to_bits()),
+                    (TokenKind::String, _) => Ctor::Str(string_value(self.text(token))),
+                    _ => match self.range_bound(ast) {
+                        Some(value) => Ctor::Range(value, value),
+                        None => return Pat::Wild,
+                    },
+                };
+                Pat::Ctor(ctor, Vec::new())
+            }
+            Ast::Unop(_, Ast::Literal(token)) => match (token.value, self.range_bound(ast)) {
+                (TokenValue::Float(value), _) => Pat::Ctor(Ctor::Float((-value).to_bits()), Vec::new()),
+                (_, Some(value)) => Pat::Ctor(Ctor::Range(value, value), Vec::new()),
+                _ => Pat::Wild,
+            },
+            Ast::RangePattern(lo, op, hi) => {
+                let (Some(lo_value), Some(hi_value)) = (self.range_bound(lo), self.range_bound(hi)) else {
+                    error!(self, &self.nodes.node_id(ast), "range patterns must be bounded by integer or character literals");
+                    return Pat::Wild
+                };
+                let hi_value = match op.kind {
+                    TokenKind::DotDot => hi_value - 1,
+                    _ => hi_value,
+                };
+                if lo_value > hi_value {
+                    error!(self, &self.nodes.node_id(ast), "range pattern is empty, the lower bound exceeds the upper bound");
+                    return Pat::Or(Vec::new())
+                }
+                Pat::Ctor(Ctor::Range(lo_value, hi_value), Vec::new())
+            }
+            Ast::TuplePattern(_, items, _) => {
+                Pat::Ctor(Ctor::Tuple(items.len()), items.iter().map(|item| self.lower(item)).collect())
+            }
+            Ast::OrPattern(alts) => Pat::Or(alts.iter().map(|alt| self.lower(alt)).collect()),
+            Ast::StructPattern(base, _, fields, _) => self.lower_struct(ast, base, fields),
+            Ast::Path(_, _, name) => Pat::Ctor(Ctor::Const(self.name(name)), Vec::new()),
+            _ => Pat::Wild,
+        }
+    }
+
+    fn lower_struct(&self, ast: &Ast, base: &Ast, fields: &[Ast]) -> Pat {
+        let Some(name) = (match base {
+            Ast::Name(name) | Ast::Path(_, _, name) => Some(name),
+            _ => None,
+        }) else {
+            return Pat::Wild
+        };
+
+        let class = self.name(name);
+        let Some(declared) = self.classes.get(&class) else {
+            error!(self, &self.nodes.node_id(base), "cannot find class `{}` for this pattern", class);
+            return Pat::Wild
+        };
+
+        let mut args = vec![None; declared.len()];
+        let mut rest = false;
+        for field_ast in fields {
+            match field_ast {
+                Ast::Rest(_) => rest = true,
+                Ast::FieldPattern(token, pattern) => {
+                    let field = self.name(token);
+                    let Some(index) = declared.iter().position(|f| *f == field) else {
+                        error!(self, &self.nodes.node_id(field_ast), "`{}` has no field `{}`", class, field);
+                        continue;
+                    };
+                    if args[index].is_some() {
+                        error!(self, &self.nodes.node_id(field_ast), "field `{}` is matched more than once", field);
+                    }
+                    args[index] = Some(pattern.map_or(Pat::Wild, |p| self.lower(p)));
+                }
+                _ => {}
+            }
+        }
+
+        let missing = declared.iter().zip(&args).filter(|(_, a)| a.is_none()).map(|(f, _)| format!("`{}`", f)).collect::<Vec<_>>();
+        if !rest && !missing.is_empty() {
+            error!(self, &self.nodes.node_id(ast), "pattern does not mention {}, use `..` to ignore the remaining fields", missing.join(", "));
+        }
+
+        let args = args.into_iter().map(|a| a.unwrap_or(Pat::Wild)).collect();
+        Pat::Ctor(Ctor::Struct(class, declared.len()), args)
+    }
+
+    fn format_witness(&self, ctor: &Ctor, args: &[String]) -> String {
+        match ctor {
+            Ctor::Bool(value) => value.to_string(),
+            Ctor::Tuple(1) => format!("({},)", args[0]),
+            Ctor::Tuple(_) => format!("({})", args.join(", ")),
+            Ctor::Struct(name, _) => {
+                let fields = &self.classes[name];
+                let mut parts = fields
+                    .iter()
+                    .zip(args)
+                    .filter(|(_, a)| *a != "_")
+                    .map(|(f, a)| format!("{}: {}", f, a))
+                    .collect::<Vec<_>>();
+                parts.push("..".to_string());
+                format!("{} {{ {} }}", name, parts.join(", "))
+            }
+            _ => "_".to_string(),
+        }
+    }
+
+    // Find a value not matched by any row, as `n` pattern strings.
+    fn missing(&self, matrix: &[Row], n: usize) -> Option<Vec<String>> {
+        if n == 0 {
+            return matrix.is_empty().then(Vec::new)
+        }
+
+        match complete_signature(&column(matrix)) {
+            Some(ctors) => ctors.iter().find_map(|ctor| {
+                let arity = ctor.arity();
+                let mut witness = self.missing(&specialize(matrix, ctor), arity + n - 1)?;
+                let head = self.format_witness(ctor, &witness[..arity]);
+                witness.splice(..arity, [head]);
+                Some(witness)
+            }),
+            None => {
+                let mut witness = self.missing(&default_matrix(matrix), n - 1)?;
+                witness.insert(0, "_".to_string());
+                Some(witness)
+            }
+        }
+    }
+
+    fn check_match(&self, ast: &Ast, arms: &[Ast]) {
+        let mut rows = Vec::new();
+        for arm in arms {
+            let Ast::Arm(pattern, guard, _, _) = arm else { continue };
+            let pat = self.lower(pattern);
+            if !useful(&rows, std::slice::from_ref(&pat)) {
+                warning!(self, &self.nodes.node_id(pattern), "unreachable match arm, earlier arms already match every value here");
+            }
+            if guard.is_none() {
+                rows.push(vec![pat]);
+            }
+        }
+
+        if let Some(witness) = self.missing(&rows, 1) {
+            error!(self, &self.nodes.node_id(ast), "non-exhaustive match, pattern `{}` is not covered", witness[0]);
+        }
+    }
+}
+
+impl<'a, 'r> Visitor<'r> for Checker<'a> {
+    fn visit_expr(&mut self, ast: &'r Ast<'r>) {
+        if let Ast::Match(_, _, _, arms, _) = ast {
+            self.check_match(ast, arms);
+        }
+        walk_expr(self, ast);
+    }
+}
+
+// Collects every class up front, so patterns may name classes declared later.
+struct Classes<'c, 'a>(&'c mut Checker<'a>);
+
+impl<'c, 'a, 'r> Visitor<'r> for Classes<'c, 'a> {
+    fn visit_decl(&mut self, ast: &'r Ast<'r>) {
+        self.0.collect_class(ast);
+        walk_decl(self, ast);
+    }
+}
+
+impl<'a> ForwardErrors for Checker<'a> {
+    fn errors(&self) -> &dyn Errors {
+        self.errors
+    }
+}
+
+pub fn check_patterns(errors: &dyn Errors, source: &[u8], nodes: &NodeMap) {
+    let root = nodes.tree().root();
+    let mut checker = Checker {
+        errors,
+        source,
+        nodes,
+        classes: HashMap::new(),
+    };
+    Classes(&mut checker).visit_root(root);
+    checker.visit_root(root);
+}
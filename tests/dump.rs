@@ -0,0 +1,129 @@
+use std::mem::{discriminant, Discriminant};
+use std::path::Path;
+
+use ebi::ast::{
+    walk_arm, walk_decl, walk_expr, walk_generic_param, walk_generics, walk_param, walk_pattern, walk_root, walk_signature,
+    walk_stmt, walk_type, Ast, AstRoot, TokenKind, Visitor,
+};
+use ebi::dump::{dump, Indent};
+use ebi::front::{parse, tokenize};
+use ebi::Compiler;
+
+fn parse_source(compiler: &Compiler, name: &str, source: &[u8]) -> AstRoot {
+    let file = compiler.add_file(name, source.to_vec());
+    parse(compiler, tokenize(compiler, file.file(), file.data()))
+}
+
+// Records the kind of every node in the order a `Visitor` meets them.
+struct Shape<'a>(Vec<Discriminant<Ast<'a>>>);
+
+macro_rules! record {
+    ($($visit:ident => $walk:ident),*) => {
+        $(fn $visit(&mut self, ast: &'a Ast<'a>) {
+            self.0.push(discriminant(ast));
+            $walk(self, ast)
+        })*
+    };
+}
+
+impl<'a> Visitor<'a> for Shape<'a> {
+    record!(
+        visit_root => walk_root,
+        visit_decl => walk_decl,
+        visit_generics => walk_generics,
+        visit_generic_param => walk_generic_param,
+        visit_signature => walk_signature,
+        visit_param => walk_param,
+        visit_stmt => walk_stmt,
+        visit_expr => walk_expr,
+        visit_arm => walk_arm,
+        visit_pattern => walk_pattern,
+        visit_type => walk_type
+    );
+}
+
+fn shape<'a>(root: &'a AstRoot) -> Vec<Discriminant<Ast<'a>>> {
+    let mut shape = Shape(Vec::new());
+    shape.visit_root(root.root());
+    shape.0
+}
+
+// Tokens that only separate items; the printer is free to choose between them.
+fn tokens(compiler: &Compiler, source: &[u8]) -> Vec<(TokenKind, String)> {
+    let file = compiler.add_file("tokens.ebi", source.to_vec());
+    tokenize(compiler, file.file(), file.data())
+        .filter(|t| !matches!(t.kind, TokenKind::Newline | TokenKind::Semicolon | TokenKind::Comma | TokenKind::End))
+        .map(|t| (t.kind, String::from_utf8_lossy(&source[t.span.begin()..t.span.end()]).trim_end().to_string()))
+        .collect()
+}
+
+fn round_trip(name: &str, source: &[u8]) {
+    let compiler = Compiler::new();
+    let root = parse_source(&compiler, name, source);
+    assert!(compiler.errors().is_empty(), "{}: {:?}", name, compiler.errors().iter().map(|e| &e.message).collect::<Vec<_>>());
+
+    let printed = dump(source, &root, Indent::default());
+    let reparsed = parse_source(&compiler, "printed.ebi", printed.as_bytes());
+    assert!(compiler.errors().is_empty(), "{} printed with errors:\n{}", name, printed);
+
+    assert_eq!(shape(&root), shape(&reparsed), "{} changed shape:\n{}", name, printed);
+
+    assert_eq!(tokens(&compiler, source), tokens(&compiler, printed.as_bytes()), "{}:\n{}", name, printed);
+
+    // Printing is idempotent.
+    assert_eq!(dump(printed.as_bytes(), &reparsed, Indent::default()), printed);
+}
+
+fn round_trip_dir(dir: &Path) -> usize {
+    let mut count = 0;
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|e| e == "ebi") {
+            round_trip(&path.display().to_string(), &std::fs::read(&path).unwrap());
+            count += 1;
+        }
+    }
+    count
+}
+
+#[test]
+fn dump_round_trip_data() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    assert!(round_trip_dir(&dir.join("data")) > 0);
+    assert!(round_trip_dir(&dir.join("tests/corpus")) > 0);
+}
+
+#[test]
+fn dump_canonical_layout() {
+    let source = "import a.b;  fn f( x:Int )->Int{ let y=x+1 ; return match y { 1=>2, _=>{y} } }\n// gone\nclass C<T:Eq>{x:T\ny:[T]?=null\nfn g(){ }}\ng( 1 ,2, )\n";
+    let compiler = Compiler::new();
+    let root = parse_source(&compiler, "internal.ebi", source.as_bytes());
+    assert!(compiler.errors().is_empty());
+
+    assert_eq!(dump(source.as_bytes(), &root, Indent::default()), "import a.b
+
+fn f(x: Int) -> Int {
+    let y = x + 1
+    return match y {
+        1 => 2
+        _ => {
+            y
+        }
+    }
+}
+
+class C<T: Eq> {
+    x: T
+    y: [T]? = null
+
+    fn g() {}
+}
+
+g(1, 2)
+");
+
+    let source = "fn f() {\n    if a {\n        b\n    }\n}\n";
+    let root = parse_source(&compiler, "internal.ebi", source.as_bytes());
+    assert_eq!(dump(source.as_bytes(), &root, Indent::Spaces(2)), "fn f() {\n  if a {\n    b\n  }\n}\n");
+    assert_eq!(dump(source.as_bytes(), &root, Indent::Tabs), "fn f() {\n\tif a {\n\t\tb\n\t}\n}\n");
+}
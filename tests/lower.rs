@@ -0,0 +1,181 @@
+use ebi::Compiler;
+use ebi::SourceSpan;
+use ebi::ast::*;
+use ebi::front::{lower, parse, tokenize};
+
+fn lower_source(source: &str) -> (Compiler, Root) {
+    let compiler = Compiler::new();
+    let file = compiler.add_file("internal.ebi", source.bytes().collect());
+    let root = parse(&compiler, tokenize(&compiler, file.file(), file.data()));
+    let lowered = lower(file.data(), &root);
+    (compiler, lowered)
+}
+
+fn text(compiler: &Compiler, span: SourceSpan) -> String {
+    compiler.span_info(span).unwrap().text().into_owned()
+}
+
+#[test]
+fn lower_declarations() {
+    let source = "//! Shapes
+import geo.point
+import util from \"lib/util\"
+
+/// A box
+pub class Box<T: Eq>: Base {
+    pub value: T? = null
+    fn get(self: Box<T>) -> [T] { return self.value }
+}
+struct Unit {}
+print(1)
+";
+    let (compiler, root) = lower_source(source);
+    assert!(compiler.errors().is_empty());
+    assert_eq!(root.docs, vec!["Shapes"]);
+    assert_eq!(root.decls.len(), 5);
+
+    let DeclKind::Import(import) = &*root.decls[0].kind else { panic!() };
+    let path = import.path.iter().map(|i| i.name.as_str()).collect::<Vec<_>>();
+    assert_eq!((path, import.source.as_deref()), (vec!["geo", "point"], None));
+    let DeclKind::Import(import) = &*root.decls[1].kind else { panic!() };
+    assert_eq!((import.path[0].name.as_str(), import.source.as_deref()), ("util", Some("lib/util")));
+
+    let DeclKind::Class(class) = &*root.decls[2].kind else { panic!() };
+    assert_eq!((class.kind, class.vis, class.name.name.as_str()), (ClassKind::Class, Visibility::Public, "Box"));
+    assert_eq!(class.docs, vec!["A box"]);
+    assert_eq!(class.generics[0].name.name, "T");
+    assert!(matches!(&*class.generics[0].bounds[0].kind, TypeKind::Named(name) if name.name == "Eq"));
+    assert!(matches!(class.base.as_ref().map(|b| &*b.kind), Some(TypeKind::Named(name)) if name.name == "Base"));
+    assert_eq!(class.members.len(), 2);
+
+    let DeclKind::Field(field) = &*class.members[0].kind else { panic!() };
+    assert_eq!(field.vis, Visibility::Public);
+    assert!(matches!(&*field.ty.kind, TypeKind::Optional(inner) if matches!(&*inner.kind, TypeKind::Named(_))));
+    assert!(matches!(field.init.as_ref().map(|i| &*i.kind), Some(ExprKind::Literal(Literal::Null))));
+    assert_eq!(text(&compiler, class.members[0].span), "pub value: T? = null");
+
+    let DeclKind::Fn(method) = &*class.members[1].kind else { panic!() };
+    assert_eq!(method.params[0].name.name, "self");
+    assert!(matches!(method.params[0].ty.as_ref().map(|t| &*t.kind), Some(TypeKind::Generic(_, args)) if args.len() == 1));
+    assert!(matches!(method.ret.as_ref().map(|t| &*t.kind), Some(TypeKind::Array(_))));
+    let StmtKind::Return(Some(value)) = &*method.body.stmts[0].kind else { panic!() };
+    assert!(matches!(&*value.kind, ExprKind::Member(_, name) if name.name == "value"));
+    assert_eq!(text(&compiler, method.body.span), "{ return self.value }");
+
+    let DeclKind::Class(unit) = &*root.decls[3].kind else { panic!() };
+    assert_eq!(unit.kind, ClassKind::Struct);
+    assert!(matches!(&*root.decls[4].kind, DeclKind::Expr(expr) if matches!(&*expr.kind, ExprKind::Call(_, args) if args.len() == 1)));
+}
+
+#[test]
+fn lower_statements_and_expressions() {
+    let source = "fn f() {
+    var x: Int = -(1 + 2) * y[0]
+    if x < 0 { break } else if a { continue } else { x += 1 }
+    while true {}
+    for i in items { g(|a, b: Int| a + b) }
+    let r = match p {
+        Point { x: 0, y, .. } if y > 0 => 1
+        (1..=5, _) | State::Idle => 'c'
+        -1 => { 0.5 }
+    }
+}
+";
+    let (compiler, root) = lower_source(source);
+    assert!(compiler.errors().is_empty());
+    let DeclKind::Fn(f) = &*root.decls[0].kind else { panic!() };
+    let stmts = &f.body.stmts;
+    assert_eq!(stmts.len(), 5);
+
+    let StmtKind::Let(Mutability::Mutable, name, Some(_), Some(init)) = &*stmts[0].kind else { panic!() };
+    assert_eq!(name.name, "x");
+    let ExprKind::BinaryOp(BinaryOp::Mul, lhs, rhs) = &*init.kind else { panic!() };
+    let ExprKind::UnaryOp(UnaryOp::Neg, inner) = &*lhs.kind else { panic!() };
+    // Parentheses disappear but keep their span on the inner expression.
+    assert!(matches!(&*inner.kind, ExprKind::BinaryOp(BinaryOp::Add, _, _)));
+    assert_eq!(text(&compiler, inner.span), "(1 + 2)");
+    assert!(matches!(&*rhs.kind, ExprKind::Index(_, index) if matches!(&*index.kind, ExprKind::Literal(Literal::Integer(0)))));
+
+    let StmtKind::If(_, then, Some(otherwise)) = &*stmts[1].kind else { panic!() };
+    assert!(matches!(&*then.stmts[0].kind, StmtKind::Break));
+    let StmtKind::If(_, _, Some(last)) = &*otherwise.kind else { panic!() };
+    let StmtKind::Block(block) = &*last.kind else { panic!() };
+    assert!(matches!(&*block.stmts[0].kind, StmtKind::Expr(e) if matches!(&*e.kind, ExprKind::BinaryOp(BinaryOp::AddAssign, _, _))));
+
+    assert!(matches!(&*stmts[2].kind, StmtKind::While(_, body) if body.stmts.is_empty()));
+
+    let StmtKind::For(i, _, body) = &*stmts[3].kind else { panic!() };
+    assert_eq!(i.name, "i");
+    let StmtKind::Expr(call) = &*body.stmts[0].kind else { panic!() };
+    let ExprKind::Call(_, args) = &*call.kind else { panic!() };
+    let ExprKind::Closure(closure) = &*args[0].kind else { panic!() };
+    assert!(closure.params[0].ty.is_none() && closure.params[1].ty.is_some());
+    assert_eq!(text(&compiler, args[0].span), "|a, b: Int| a + b");
+
+    let StmtKind::Let(Mutability::Immutable, _, None, Some(value)) = &*stmts[4].kind else { panic!() };
+    let ExprKind::Match(_, arms) = &*value.kind else { panic!() };
+    assert_eq!(arms.len(), 3);
+
+    let PatternKind::Struct(path, fields, true) = &*arms[0].pattern.kind else { panic!() };
+    assert_eq!(path[0].name, "Point");
+    assert!(matches!(&*fields[0].pattern.kind, PatternKind::Literal(_)));
+    assert!(matches!(&*fields[1].pattern.kind, PatternKind::Bind(name) if name.name == "y"));
+    assert!(arms[0].guard.is_some());
+
+    let PatternKind::Or(alts) = &*arms[1].pattern.kind else { panic!() };
+    let PatternKind::Tuple(items) = &*alts[0].kind else { panic!() };
+    assert!(matches!(&*items[0].kind, PatternKind::Range(_, RangeEnd::Inclusive, _)));
+    assert!(matches!(&*items[1].kind, PatternKind::Wildcard));
+    assert!(matches!(&*alts[1].kind, PatternKind::Path(path) if path.len() == 2));
+    assert!(matches!(&*arms[1].body.kind, ExprKind::Literal(Literal::Char('c'))));
+
+    assert!(matches!(&*arms[2].pattern.kind, PatternKind::Literal(e) if matches!(&*e.kind, ExprKind::UnaryOp(UnaryOp::Neg, _))));
+    assert!(matches!(&*arms[2].body.kind, ExprKind::Block(_)));
+    assert_eq!(text(&compiler, arms[2].span), "-1 => { 0.5 }");
+}
+
+#[test]
+fn lower_range_bounds() {
+    // Range bounds must be literals, a name parses as a binding and has no expression form.
+    let (compiler, root) = lower_source("fn f() {\n    match c {\n        'a'..-1 => 1\n        A..=B => 2\n    }\n}\n");
+    assert!(compiler.errors().is_empty());
+    let DeclKind::Fn(f) = &*root.decls[0].kind else { panic!() };
+    let StmtKind::Expr(value) = &*f.body.stmts[0].kind else { panic!() };
+    let ExprKind::Match(_, arms) = &*value.kind else { panic!() };
+
+    let PatternKind::Range(lo, RangeEnd::Exclusive, hi) = &*arms[0].pattern.kind else { panic!() };
+    assert!(matches!(&*lo.kind, ExprKind::Literal(Literal::Char('a'))));
+    assert!(matches!(&*hi.kind, ExprKind::UnaryOp(UnaryOp::Neg, _)));
+    let PatternKind::Range(lo, RangeEnd::Inclusive, hi) = &*arms[1].pattern.kind else { panic!() };
+    assert!(matches!((&*lo.kind, &*hi.kind), (ExprKind::Error, ExprKind::Error)));
+    assert_eq!(text(&compiler, lo.span), "A");
+}
+
+#[test]
+fn lower_errors() {
+    let (compiler, root) = lower_source("fn f(a: ) {\n    let = 1\n}\nx y\n");
+    assert!(!compiler.errors().is_empty());
+
+    let DeclKind::Fn(f) = &*root.decls[0].kind else { panic!() };
+    assert!(matches!(f.params[0].ty.as_ref().map(|t| &*t.kind), Some(TypeKind::Error)));
+    assert!(matches!(&*f.body.stmts[0].kind, StmtKind::Error));
+    assert_eq!(text(&compiler, f.body.stmts[0].span), "let = 1");
+
+    assert!(matches!(&*root.decls[1].kind, DeclKind::Expr(_)));
+    assert!(matches!(&*root.decls[2].kind, DeclKind::Error));
+    assert_eq!(text(&compiler, root.decls[2].span), "y");
+}
+
+#[test]
+fn lower_parenthesized_types() {
+    let (compiler, root) = lower_source("fn f(x: (Undefined), y: ((Int)?)) -> (fn(Int) -> Int) {}\n");
+    assert!(compiler.errors().is_empty());
+    let DeclKind::Fn(f) = &*root.decls[0].kind else { panic!() };
+
+    let x = f.params[0].ty.as_ref().unwrap();
+    assert!(matches!(&*x.kind, TypeKind::Named(name) if name.name == "Undefined"));
+    assert_eq!(text(&compiler, x.span), "(Undefined)");
+    let y = f.params[1].ty.as_ref().unwrap();
+    assert!(matches!(&*y.kind, TypeKind::Optional(inner) if matches!(&*inner.kind, TypeKind::Named(_))));
+    assert!(matches!(f.ret.as_ref().map(|r| &*r.kind), Some(TypeKind::Fn(params, Some(_))) if params.len() == 1));
+}
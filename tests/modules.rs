@@ -0,0 +1,112 @@
+use std::path::PathBuf;
+
+use ebi::{
+    modules::{ModuleResolver, SymbolKind},
+    Compiler,
+};
+
+fn write_tree(test: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ebi-modules-{}-{}", test, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (path, source) in files {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, source).unwrap();
+    }
+    dir
+}
+
+fn messages(compiler: &Compiler) -> Vec<String> {
+    compiler.errors().into_iter().map(|e| e.message).collect()
+}
+
+#[test]
+fn modules_resolve_imports() {
+    let dir = write_tree("resolve", &[
+        ("src/main.ebi", "import geo.point\nimport helpers from \"../lib/helpers\"\nfn main() {\n    point.origin()\n}\n"),
+        ("src/geo/point.ebi", "import helpers from \"../../lib/helpers.ebi\"\npub fn origin() {}\npub struct Point {}\n"),
+        ("lib/helpers.ebi", "pub fn origin() {}\n"),
+    ]);
+
+    let compiler = Compiler::new();
+    let mut resolver = ModuleResolver::new(&compiler);
+    resolver.add_root(dir.join("src"));
+    let main = resolver.load(dir.join("src/main.ebi"));
+    resolver.check_references();
+    assert!(compiler.errors().is_empty(), "{:?}", messages(&compiler));
+
+    let names = resolver.modules().iter().map(|m| m.name()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["main", "geo.point", "helpers"]);
+
+    // Both files import `helpers` and declare `origin` without colliding.
+    let main = resolver.module(main);
+    let Some(SymbolKind::Module(point)) = main.lookup("point").map(|s| s.kind) else { panic!() };
+    let Some(SymbolKind::Module(helpers)) = main.lookup("helpers").map(|s| s.kind) else { panic!() };
+    assert_eq!(resolver.module(point).lookup("Point").map(|s| s.kind), Some(SymbolKind::Struct));
+    assert_eq!(resolver.module(point).lookup("helpers").map(|s| s.kind), Some(SymbolKind::Module(helpers)));
+    assert!(resolver.module(helpers).lookup("origin").is_some_and(|s| s.public));
+    assert!(main.lookup("origin").is_none());
+}
+
+#[test]
+fn modules_import_errors() {
+    let dir = write_tree("errors", &[
+        ("main.ebi", "import missing.thing\nimport x from \"nope\"\nimport util\nimport util\nfn util() {}\nfn f() {\n    util::Secret\n    util.nothing()\n    util.visible()\n}\n"),
+        ("util.ebi", "struct Secret {}\npub fn visible() {}\n"),
+    ]);
+
+    let compiler = Compiler::new();
+    let mut resolver = ModuleResolver::new(&compiler);
+    resolver.add_root(&dir);
+    resolver.load(dir.join("main.ebi"));
+    resolver.check_references();
+
+    let root = format!("`{}`", dir.display());
+    assert_eq!(messages(&compiler), vec![
+        format!("cannot find module `missing.thing` in source roots {}", root),
+        "cannot find file `nope` imported as `x`".to_string(),
+        "`util` is already defined in this module".to_string(),
+        "`util` is already defined in this module".to_string(),
+        "`Secret` is private to module `util`".to_string(),
+        "module `util` has no item named `nothing`".to_string(),
+    ]);
+}
+
+#[test]
+fn modules_import_cycle() {
+    let dir = write_tree("cycle", &[
+        ("a.ebi", "import b\n"),
+        ("b.ebi", "import c\n"),
+        ("c.ebi", "import a\nimport b\n"),
+    ]);
+
+    let compiler = Compiler::new();
+    let mut resolver = ModuleResolver::new(&compiler);
+    resolver.add_root(&dir);
+    resolver.load(dir.join("a.ebi"));
+
+    assert_eq!(resolver.modules().len(), 3);
+    assert_eq!(messages(&compiler), vec![
+        "import cycle detected: `a` -> `b` -> `c` -> `a`",
+        "import cycle detected: `b` -> `c` -> `b`",
+    ]);
+    let span = compiler.span_info(compiler.errors()[1].location).unwrap();
+    assert_eq!((span.filename().ends_with("c.ebi"), span.line(), span.column()), (true, 2, 8));
+}
+
+#[test]
+fn modules_locals_shadow_imports() {
+    let dir = write_tree("shadow", &[
+        ("main.ebi", "import geo.point\nfn f(point: P) {\n    point.x\n}\nfn g() {\n    let point = 1\n    point.y\n    for point in ps { point.z }\n    each(|point| point.w)\n    match p {\n        Some { point } => point.v\n    }\n    {\n        let inner = 1\n    }\n}\nfn h() {\n    point.missing\n}\n"),
+        ("geo/point.ebi", "pub fn origin() {}\n"),
+    ]);
+
+    let compiler = Compiler::new();
+    let mut resolver = ModuleResolver::new(&compiler);
+    resolver.add_root(&dir);
+    resolver.load(dir.join("main.ebi"));
+    resolver.check_references();
+
+    // Only the reference outside of any shadowing binding is checked.
+    assert_eq!(messages(&compiler), vec!["module `geo.point` has no item named `missing`"]);
+}
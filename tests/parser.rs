@@ -0,0 +1,706 @@
+use bumpalo::Bump;
+use ebi::Compiler;
+use ebi::ast::{walk_expr, walk_fold_expr, walk_type, Ast, AstRoot, Folder, NodeId, Token, TokenKind, Visitor};
+use ebi::compiler::{push_error, InternalLocation};
+use ebi::front::{parse, tokenize};
+
+fn parse_source(source: &str) -> (Compiler, AstRoot) {
+    let compiler = Compiler::new();
+    let file = compiler.add_file("internal.ebi", source.bytes().collect());
+    let root = parse(&compiler, tokenize(&compiler, file.file(), file.data()));
+    (compiler, root)
+}
+
+#[test]
+fn parser_doc_comments() {
+    let source = "//! Module docs\n\n/// A class\n/// with docs\nclass A {\n    //! Inner\n}\nstruct B {}\n";
+    let (compiler, root) = parse_source(source);
+    assert!(compiler.errors().is_empty());
+
+    let Ast::Root(docs, decls) = root.root() else { panic!() };
+    assert_eq!(docs.len(), 1);
+    assert_eq!(docs[0].kind, TokenKind::InnerDocComment);
+    assert_eq!(decls.len(), 2);
+
+    let Ast::ClassDecl(docs, _, _, _, _, _, _) = &decls[0] else { panic!() };
+    let kinds = docs.iter().map(|t| t.kind).collect::<Vec<_>>();
+    assert_eq!(kinds, vec![TokenKind::DocComment, TokenKind::DocComment, TokenKind::InnerDocComment]);
+
+    let Ast::ClassDecl(docs, _, _, _, _, _, _) = &decls[1] else { panic!() };
+    assert!(docs.is_empty());
+}
+
+fn text(compiler: &Compiler, token: &Token) -> String {
+    compiler.span_info(token.span).unwrap().text().into_owned()
+}
+
+fn sexpr(compiler: &Compiler, ast: &Ast) -> String {
+    match ast {
+        Ast::Error(_) => "<error>".to_string(),
+        Ast::Name(t) | Ast::Literal(t) => text(compiler, t),
+        Ast::Paren(_, e, _) => format!("({})", sexpr(compiler, e)),
+        Ast::Unop(op, e) => format!("({} {})", text(compiler, op), sexpr(compiler, e)),
+        Ast::Binop(op, l, r) => format!("({} {} {})", text(compiler, op), sexpr(compiler, l), sexpr(compiler, r)),
+        Ast::Call(f, _, args, _) => {
+            let args = args.iter().map(|a| format!(" {}", sexpr(compiler, a))).collect::<String>();
+            format!("(call {}{})", sexpr(compiler, f), args)
+        }
+        Ast::Member(e, _, name) => format!("(. {} {})", sexpr(compiler, e), text(compiler, name)),
+        Ast::Index(e, _, i, _) => format!("([] {} {})", sexpr(compiler, e), sexpr(compiler, i)),
+        Ast::FnDecl(_, vis, _, name, generics, sig, body) => {
+            let vis = if vis.is_some() { "pub " } else { "" };
+            let generics = generics.map(|g| sexpr(compiler, g)).unwrap_or_default();
+            format!("({}fn {}{} {} {})", vis, text(compiler, name), generics, sexpr(compiler, sig), sexpr(compiler, body))
+        }
+        Ast::Generics(_, params, _) => {
+            let params = params.iter().map(|p| sexpr(compiler, p)).collect::<Vec<_>>().join(", ");
+            format!("<{}>", params)
+        }
+        Ast::GenericParam(name, bounds) => match bounds.is_empty() {
+            true => text(compiler, name),
+            false => {
+                let bounds = bounds.iter().map(|b| sexpr(compiler, b)).collect::<Vec<_>>().join(" + ");
+                format!("{}: {}", text(compiler, name), bounds)
+            }
+        },
+        Ast::Signature(_, params, _, ret) => {
+            let params = params.iter().map(|p| sexpr(compiler, p)).collect::<Vec<_>>().join(" ");
+            match ret {
+                Some(ret) => format!("({}) -> {}", params, sexpr(compiler, ret)),
+                None => format!("({})", params),
+            }
+        }
+        Ast::Param(name, ty) => format!("{}: {}", text(compiler, name), sexpr(compiler, ty)),
+        Ast::Block(_, items, _) => {
+            let items = items.iter().map(|i| format!(" {}", sexpr(compiler, i))).collect::<String>();
+            format!("{{{}}}", items)
+        }
+        Ast::ClassDecl(_, vis, kw, name, generics, base, decls) => {
+            let vis = if vis.is_some() { "pub " } else { "" };
+            let generics = generics.map(|g| sexpr(compiler, g)).unwrap_or_default();
+            let base = base.map(|b| format!(": {}", sexpr(compiler, b))).unwrap_or_default();
+            let decls = decls.iter().map(|d| format!(" {}", sexpr(compiler, d))).collect::<String>();
+            format!("({}{} {}{}{}{})", vis, text(compiler, kw), text(compiler, name), generics, base, decls)
+        }
+        Ast::Field(_, vis, name, ty, init) => {
+            let vis = if vis.is_some() { "pub " } else { "" };
+            let init = init.map(|i| format!(" = {}", sexpr(compiler, i))).unwrap_or_default();
+            format!("({}field {}: {}{})", vis, text(compiler, name), sexpr(compiler, ty), init)
+        }
+        Ast::Let(kw, name, ty, init) => {
+            let ty = ty.map(|t| format!(": {}", sexpr(compiler, t))).unwrap_or_default();
+            let init = init.map(|i| format!(" = {}", sexpr(compiler, i))).unwrap_or_default();
+            format!("({} {}{}{})", text(compiler, kw), text(compiler, name), ty, init)
+        }
+        Ast::If(_, cond, then, otherwise) => {
+            let otherwise = otherwise.map(|o| format!(" else {}", sexpr(compiler, o))).unwrap_or_default();
+            format!("(if {} {}{})", sexpr(compiler, cond), sexpr(compiler, then), otherwise)
+        }
+        Ast::While(_, cond, body) => format!("(while {} {})", sexpr(compiler, cond), sexpr(compiler, body)),
+        Ast::For(_, name, iter, body) => {
+            format!("(for {} {} {})", text(compiler, name), sexpr(compiler, iter), sexpr(compiler, body))
+        }
+        Ast::Return(_, value) => match value {
+            Some(value) => format!("(return {})", sexpr(compiler, value)),
+            None => "(return)".to_string(),
+        },
+        Ast::Break(_) => "(break)".to_string(),
+        Ast::Continue(_) => "(continue)".to_string(),
+        Ast::Path(base, _, name) => format!("{}::{}", sexpr(compiler, base), text(compiler, name)),
+        Ast::Generic(base, _, args, _) => {
+            let args = args.iter().map(|a| sexpr(compiler, a)).collect::<Vec<_>>().join(", ");
+            format!("{}<{}>", sexpr(compiler, base), args)
+        }
+        Ast::ArrayType(_, elem, _) => format!("[{}]", sexpr(compiler, elem)),
+        Ast::OptionalType(ty, _) => format!("{}?", sexpr(compiler, ty)),
+        Ast::TupleType(_, types, _) => {
+            let types = types.iter().map(|t| format!("{},", sexpr(compiler, t))).collect::<String>();
+            format!("({})", types)
+        }
+        Ast::FnType(_, _, params, _, ret) => {
+            let params = params.iter().map(|p| sexpr(compiler, p)).collect::<Vec<_>>().join(", ");
+            let ret = ret.map(|r| format!(" -> {}", sexpr(compiler, r))).unwrap_or_default();
+            format!("fn({}){}", params, ret)
+        }
+        Ast::Match(_, scrutinee, _, arms, _) => {
+            let arms = arms.iter().map(|a| format!(" {}", sexpr(compiler, a))).collect::<String>();
+            format!("(match {}{})", sexpr(compiler, scrutinee), arms)
+        }
+        Ast::Arm(pattern, guard, _, body) => {
+            let guard = guard.map(|g| format!(" if {}", sexpr(compiler, g))).unwrap_or_default();
+            format!("[{}{} => {}]", sexpr(compiler, pattern), guard, sexpr(compiler, body))
+        }
+        Ast::Wildcard(_) => "_".to_string(),
+        Ast::Bind(name) => format!("${}", text(compiler, name)),
+        Ast::Rest(_) => "..".to_string(),
+        Ast::StructPattern(base, _, fields, _) => {
+            let fields = fields.iter().map(|f| sexpr(compiler, f)).collect::<Vec<_>>().join(", ");
+            format!("{} {{ {} }}", sexpr(compiler, base), fields)
+        }
+        Ast::FieldPattern(name, pattern) => match pattern {
+            Some(pattern) => format!("{}: {}", text(compiler, name), sexpr(compiler, pattern)),
+            None => text(compiler, name),
+        },
+        Ast::TuplePattern(_, items, _) => {
+            let items = items.iter().map(|i| format!("{},", sexpr(compiler, i))).collect::<String>();
+            format!("({})", items)
+        }
+        Ast::OrPattern(alts) => alts.iter().map(|a| sexpr(compiler, a)).collect::<Vec<_>>().join(" | "),
+        Ast::RangePattern(lo, op, hi) => format!("{}{}{}", sexpr(compiler, lo), text(compiler, op), sexpr(compiler, hi)),
+        Ast::Closure(kw, sig, body) => {
+            let kw = if kw.is_some() { "fn" } else { "closure" };
+            format!("({} {} {})", kw, sexpr(compiler, sig), sexpr(compiler, body))
+        }
+        Ast::Import(_, path, source) => {
+            let path = path.iter().map(|t| text(compiler, t)).collect::<Vec<_>>().join(".");
+            let source = source.as_ref().map(|s| format!(" from {}", text(compiler, s))).unwrap_or_default();
+            format!("(import {}{})", path, source)
+        }
+        _ => format!("{:?}", ast),
+    }
+}
+
+fn parse_exprs(source: &str) -> (Compiler, Vec<String>) {
+    let (compiler, root) = parse_source(source);
+    let Ast::Root(_, decls) = root.root() else { panic!() };
+    let exprs = decls.iter().map(|d| sexpr(&compiler, d)).collect();
+    (compiler, exprs)
+}
+
+#[test]
+fn parser_precedence() {
+    let cases = [
+        ("a + b * c", "(+ a (* b c))"),
+        ("a * b + c", "(+ (* a b) c)"),
+        ("a - b - c", "(- (- a b) c)"),
+        ("a = b = c", "(= a (= b c))"),
+        ("a += b * 2", "(+= a (* b 2))"),
+        ("a || b && c", "(|| a (&& b c))"),
+        ("a == b && c < d", "(&& (== a b) (< c d))"),
+        ("a | b ^ c & d", "(| a (^ b (& c d)))"),
+        ("a << 1 + b", "(<< a (+ 1 b))"),
+        ("a & b == c", "(== (& a b) c)"),
+        ("-a * b", "(* (- a) b)"),
+        ("!-~a", "(! (- (~ a)))"),
+        ("(a + b) * c", "(* ((+ a b)) c)"),
+        ("x = true || null", "(= x (|| true null))"),
+        ("a +\n    b", "(+ a b)"),
+        ("(a\n  + b\n)", "((+ a b))"),
+        ("1.5 % 'c' / \"s\"", "(/ (% 1.5 'c') \"s\")"),
+    ];
+    for (source, expected) in cases {
+        let (compiler, exprs) = parse_exprs(source);
+        assert!(compiler.errors().is_empty(), "{}", source);
+        assert_eq!(exprs, vec![expected.to_string()], "{}", source);
+    }
+}
+
+#[test]
+fn parser_expression_errors() {
+    let (compiler, exprs) = parse_exprs("a < b < c");
+    let errors = compiler.errors();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.starts_with("comparison operators cannot be chained"));
+    assert_eq!(exprs, vec!["(< (< a b) c)"]);
+
+    let (compiler, _) = parse_exprs("(a < b) < c\na == b != c");
+    assert_eq!(compiler.errors().len(), 1);
+
+    let (compiler, exprs) = parse_exprs("(a + )");
+    let errors = compiler.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "expected an expression after `+`, found `)`");
+    assert_eq!(exprs, vec!["((+ a <error>))"]);
+
+    let (compiler, _) = parse_exprs("(a + b\n");
+    let errors = compiler.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "expected `)` to close `(`, found end of file");
+}
+
+#[test]
+fn parser_postfix() {
+    let cases = [
+        ("a.b(c)[d] + e", "(+ ([] (call (. a b) c) d) e)"),
+        ("f()", "(call f)"),
+        ("f(a, b + 1,)", "(call f a (+ b 1))"),
+        ("f(\n    a,\n    b\n)", "(call f a b)"),
+        ("a.b.c().d(e)(f)", "(call (call (. (call (. (. a b) c)) d) e) f)"),
+        ("-a.b[0]", "(- ([] (. a b) 0))"),
+        ("x[i][j] = y", "(= ([] ([] x i) j) y)"),
+        ("(f)(x)", "(call (f) x)"),
+    ];
+    for (source, expected) in cases {
+        let (compiler, exprs) = parse_exprs(source);
+        assert!(compiler.errors().is_empty(), "{}", source);
+        assert_eq!(exprs, vec![expected.to_string()], "{}", source);
+    }
+}
+
+#[test]
+fn parser_unclosed_call() {
+    let (compiler, exprs) = parse_exprs("f(a, b\ng(c)\nh");
+    let errors = compiler.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "unclosed argument list, expected `,` or `)`, found identifier");
+    assert_eq!(exprs, vec!["(call f a b)", "(call g c)", "h"]);
+
+    let (compiler, exprs) = parse_exprs("f(a,\n");
+    let errors = compiler.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "expected an argument, found end of file");
+    assert_eq!(exprs, vec!["(call f a)"]);
+
+    let (compiler, _) = parse_exprs("a.\nb[1");
+    let messages = compiler.errors().into_iter().map(|e| e.message).collect::<Vec<_>>();
+    assert_eq!(messages, vec![
+        "expected a member name after `.`, found newline",
+        "expected `]` to close `[`, found end of file",
+    ]);
+}
+
+#[test]
+fn parser_functions() {
+    let source = "/// Adds\nfn add(a: int, b: int) -> int {\n    a + b\n}\n\nfn main()\n{\n    print(add(1, 2))\n    0\n}\n\nclass A {\n    fn get(\n        self: A,\n    ) -> int { 1 }\n}\n";
+    let (compiler, exprs) = parse_exprs(source);
+    assert!(compiler.errors().is_empty());
+    assert_eq!(exprs, vec![
+        "(fn add (a: int b: int) -> int { (+ a b)})",
+        "(fn main () { (call print (call add 1 2)) 0})",
+        "(class A (fn get (self: A) -> int { 1}))",
+    ]);
+
+    let (_, root) = parse_source(source);
+    let Ast::Root(_, decls) = root.root() else { panic!() };
+    let Ast::FnDecl(docs, _, _, _, _, _, _) = &decls[0] else { panic!() };
+    assert_eq!(docs.len(), 1);
+}
+
+#[test]
+fn parser_function_errors() {
+    let cases = [
+        ("fn f(a, b: int) {}", "missing type for parameter, expected `:` followed by a type", "(fn f (a: <error> b: int) {})"),
+        ("fn f(a: int,, b: int) {}", "duplicated `,` in parameter list", "(fn f (a: int b: int) {})"),
+        ("fn f(a: int) -> int\nx", "expected `{` to begin the body of `fn`, found identifier", "(fn f (a: int) -> int <error>)"),
+        ("fn f(a: ) {}", "expected a type for parameter, found `)`", "(fn f (a: <error>) {})"),
+        ("fn f() -> {}", "expected a return type after `->`, found `{`", "(fn f () -> <error> {})"),
+    ];
+    for (source, message, expected) in cases {
+        let (compiler, exprs) = parse_exprs(source);
+        let messages = compiler.errors().into_iter().map(|e| e.message).collect::<Vec<_>>();
+        assert_eq!(messages, vec![message.to_string()], "{}", source);
+        assert_eq!(exprs[0], expected, "{}", source);
+    }
+
+    let (compiler, _) = parse_exprs("fn f() {\n    a\n");
+    let messages = compiler.errors().into_iter().map(|e| e.message).collect::<Vec<_>>();
+    assert_eq!(messages, vec!["unclosed block"]);
+}
+
+fn parse_body(source: &str) -> (Compiler, String) {
+    let (compiler, exprs) = parse_exprs(&format!("fn f() {{\n{}\n}}", source));
+    let body = exprs[0].strip_prefix("(fn f () ").unwrap().strip_suffix(")").unwrap().to_string();
+    (compiler, body)
+}
+
+#[test]
+fn parser_statements() {
+    let cases = [
+        ("let x = 1\nvar y: int\ny = x", "{ (let x = 1) (var y: int) (= y x)}"),
+        ("let a: int = 1; let b = 2;", "{ (let a: int = 1) (let b = 2)}"),
+        ("if a { b } else if c { d } else { e }", "{ (if a { b} else (if c { d} else { e}))}"),
+        ("while i < n {\n    i += 1\n    if done { break }\n    continue\n}", "{ (while (< i n) { (+= i 1) (if done { (break)}) (continue)})}"),
+        ("for x in items() { print(x) }", "{ (for x (call items) { (call print x)})}"),
+        ("return\nreturn a + 1;", "{ (return) (return (+ a 1))}"),
+        ("{ let x = 1 }\n\n\nf(x)", "{ { (let x = 1)} (call f x)}"),
+        ("let x =\n    1", "{ (let x = 1)}"),
+    ];
+    for (source, expected) in cases {
+        let (compiler, body) = parse_body(source);
+        assert!(compiler.errors().is_empty(), "{}: {:?}", source, compiler.errors().iter().map(|e| &e.message).collect::<Vec<_>>());
+        assert_eq!(body, expected, "{}", source);
+    }
+}
+
+#[test]
+fn parser_statement_errors() {
+    let cases = [
+        ("let x = 1 y = 2\nz", "expected one of `;`, newline, `}` but found identifier", "{ (let x = 1) <error> z}"),
+        ("let = 1\nz", "expected a name after `let`, found `=`", "{ <error> z}"),
+        ("if { x }", "expected a condition after `if`, found `{`", "{ (if <error> { x})}"),
+        ("while x\n{}", "expected `{` to begin the body of `while`, found newline", "{ (while x <error>) {}}"),
+        ("for x items {}", "expected `in` after the loop variable, found identifier", "{ <error>}"),
+        ("return )\nz", "expected an expression after `return`, found `)`", "{ (return <error>) <error> z}"),
+        ("else { x }", "expected a statement, found `else`", "{ <error>}"),
+        ("if a { b }\nelse { c }\nd", "`else` must be on the same line as the `}` closing the `if` body", "{ (if a { b}) <error> d}"),
+    ];
+    for (source, message, expected) in cases {
+        let (compiler, body) = parse_body(source);
+        let messages = compiler.errors().into_iter().map(|e| e.message).collect::<Vec<_>>();
+        assert_eq!(messages, vec![message.to_string()], "{}", source);
+        assert_eq!(body, expected, "{}", source);
+    }
+}
+
+#[test]
+fn parser_fields() {
+    let source = "pub struct Point {\n    pub x: Int = 0\n    /// Doc\n    y: Int; z: Float\n}\nclass Shape {}\nclass Circle: Shape {\n    radius: Float = 1.0\n    pub fn area(self: Circle) -> Float { radius * radius }\n}\n";
+    let (compiler, exprs) = parse_exprs(source);
+    assert!(compiler.errors().is_empty(), "{:?}", compiler.errors().iter().map(|e| &e.message).collect::<Vec<_>>());
+    assert_eq!(exprs, vec![
+        "(pub struct Point (pub field x: Int = 0) (field y: Int) (field z: Float))",
+        "(class Shape)",
+        "(class Circle: Shape (field radius: Float = 1.0) (pub fn area (self: Circle) -> Float { (* radius radius)}))",
+    ]);
+}
+
+#[test]
+fn parser_class_errors() {
+    let cases = [
+        ("class Test {\n    x + y\n    z: Int\n}", "expressions are not allowed in a class body, declare a field with `name: Type` or move the code into a method", "(class Test <error> (field z: Int))"),
+        ("class Test {\n    let x = 1\n}", "`let` cannot be used in a class body, declare a field with `name: Type`", "(class Test <error>)"),
+        ("struct A: B {}", "a struct cannot inherit from a base class, only a class can", "(struct A: B)"),
+        ("class A: B, C {}", "a class can have at most one base class", "(class A: B)"),
+        ("class A {\n    x: Int y: Int\n}", "expected one of `::`, `<`, `?`, `=`, `;`, newline, `}` but found identifier", "(class A (field x: Int) <error>)"),
+        ("class A {\n    if x { a }\n    b: Int\n}", "expected one of `}`, `pub`, identifier, `fn`, `class`, `struct` but found `if`", "(class A <error> (field b: Int))"),
+    ];
+    for (source, message, expected) in cases {
+        let (compiler, exprs) = parse_exprs(source);
+        let messages = compiler.errors().into_iter().map(|e| e.message).collect::<Vec<_>>();
+        assert_eq!(messages, vec![message.to_string()], "{}", source);
+        assert_eq!(exprs, vec![expected.to_string()], "{}", source);
+    }
+}
+
+#[test]
+fn parser_types() {
+    let cases = [
+        ("Int", "Int"),
+        ("a::b::C", "a::b::C"),
+        ("List<Int>", "List<Int>"),
+        ("Map<String, List<Int>>", "Map<String, List<Int>>"),
+        ("Map<String, List<List<Int>>>", "Map<String, List<List<Int>>>"),
+        ("[Int]?", "[Int]?"),
+        ("[Int?]", "[Int?]"),
+        ("()", "()"),
+        ("(Int)", "(Int)"),
+        ("(Int,)", "(Int,)"),
+        ("(Int, [Float])", "(Int,[Float],)"),
+        ("fn(Int, Int) -> Bool", "fn(Int, Int) -> Bool"),
+        ("fn()", "fn()"),
+        ("(fn(Int) -> Int)?", "(fn(Int) -> Int)?"),
+        ("io::Result<\n    Int,\n>", "io::Result<Int>"),
+    ];
+    for (ty, expected) in cases {
+        let (compiler, body) = parse_body(&format!("let x: {}", ty));
+        assert!(compiler.errors().is_empty(), "{}: {:?}", ty, compiler.errors().iter().map(|e| &e.message).collect::<Vec<_>>());
+        assert_eq!(body, format!("{{ (let x: {})}}", expected), "{}", ty);
+    }
+
+    let (compiler, body) = parse_body("let x: List<Int>= y");
+    assert!(compiler.errors().is_empty());
+    assert_eq!(body, "{ (let x: List<Int> = y)}");
+
+    let (compiler, body) = parse_body("let x: List<List<Int>>= y");
+    assert!(compiler.errors().is_empty());
+    assert_eq!(body, "{ (let x: List<List<Int>> = y)}");
+}
+
+#[test]
+fn parser_generic_expressions() {
+    let (compiler, exprs) = parse_exprs("a < b\nList::<Int>::new(x) >> 1\nf::<A, B>(a < b, c > d)");
+    assert!(compiler.errors().is_empty());
+    assert_eq!(exprs, vec![
+        "(< a b)",
+        "(>> (call List<Int>::new x) 1)",
+        "(call f<A, B> (< a b) (> c d))",
+    ]);
+
+    let (compiler, _) = parse_exprs("List<Int>(x)");
+    let messages = compiler.errors().into_iter().map(|e| e.message).collect::<Vec<_>>();
+    assert_eq!(messages, vec!["comparison operators cannot be chained, use `::<` to pass generic arguments in an expression"]);
+
+    let (compiler, body) = parse_body("let x: List<Int\nlet y: [Int\nz");
+    let messages = compiler.errors().into_iter().map(|e| e.message).collect::<Vec<_>>();
+    assert_eq!(messages, vec!["expected `>` to close the generic arguments, found `let`"]);
+    assert_eq!(body, "{ (let x: List<Int>) <error> z}");
+
+    let (compiler, _) = parse_body("let y: [Int\nz");
+    let messages = compiler.errors().into_iter().map(|e| e.message).collect::<Vec<_>>();
+    assert_eq!(messages, vec!["expected `]` to close the array type, found identifier"]);
+}
+
+#[test]
+fn parser_generic_params() {
+    let source = "class Box<T> {\n    value: T\n}\nfn map<T, U: Eq + Hash<T>>(list: List<T>, f: fn(T) -> U) -> List<U> {}\nclass Pair<\n    A,\n    B,\n>: Base<A> {}\n";
+    let (compiler, exprs) = parse_exprs(source);
+    assert!(compiler.errors().is_empty(), "{:?}", compiler.errors().iter().map(|e| &e.message).collect::<Vec<_>>());
+    assert_eq!(exprs, vec![
+        "(class Box<T> (field value: T))",
+        "(fn map<T, U: Eq + Hash<T>> (list: List<T> f: fn(T) -> U) -> List<U> {})",
+        "(class Pair<A, B>: Base<A>)",
+    ]);
+
+    let (compiler, _) = parse_exprs("class A<T: > {}\nfn f<1>() {}");
+    let messages = compiler.errors().into_iter().map(|e| e.message).collect::<Vec<_>>();
+    assert_eq!(messages, vec![
+        "expected a type after `:`, found `>`",
+        "expected a type parameter name, found integer literal",
+    ]);
+}
+
+#[test]
+fn parser_match() {
+    let source = "match state {
+    State::Idle => 0
+    Point { x: 0, y, .. } if y > 0 => { return y }
+    (a, _) | (_, a) => a
+    -5..=-1 | 1..10 => 1
+    'a'..='z' => 2
+    (x) => x
+    (x,) => x
+    _ => match x { _ => 3 }
+}";
+    let (compiler, exprs) = parse_exprs(source);
+    assert!(compiler.errors().is_empty(), "{:?}", compiler.errors().iter().map(|e| &e.message).collect::<Vec<_>>());
+    assert_eq!(exprs, vec![concat!(
+        "(match state",
+        " [State::Idle => 0]",
+        " [Point { x: 0, y, .. } if (> y 0) => { (return y)}]",
+        " [($a,_,) | (_,$a,) => a]",
+        " [(- 5)..=(- 1) | 1..10 => 1]",
+        " ['a'..='z' => 2]",
+        " [($x) => x]",
+        " [($x,) => x]",
+        " [_ => (match x [_ => 3])])",
+    )]);
+}
+
+#[test]
+fn parser_match_errors() {
+    let cases = [
+        ("match x {\n    1 2\n    _ => 0\n}", "expected `=>` after the match pattern, found integer literal", "(match x <error> [_ => 0])"),
+        ("match x {\n    + => 1\n}", "expected a pattern, found `+`", "(match x <error>)"),
+        ("match x {\n    P { .., y } => 1\n}", "`..` must be the last entry in a struct pattern, found `,`", "(match x [P { .., y } => 1])"),
+        ("match x {\n    1.. => 1\n}", "expected the end of the range after `..`, found `=>`", "(match x [1..<error> => 1])"),
+        ("match x {\n    a => 1 b => 2\n}", "expected one of `,`, newline, `}` but found identifier", "(match x [$a => 1] <error>)"),
+    ];
+    for (source, message, expected) in cases {
+        let (compiler, exprs) = parse_exprs(source);
+        let messages = compiler.errors().into_iter().map(|e| e.message).collect::<Vec<_>>();
+        assert_eq!(messages, vec![message.to_string()], "{}", source);
+        assert_eq!(exprs, vec![expected.to_string()], "{}", source);
+    }
+}
+
+#[test]
+fn parser_imports() {
+    let (compiler, exprs) = parse_exprs("import a.b.c\nimport util from \"../lib/util.ebi\"\nimport from\n");
+    assert!(compiler.errors().is_empty());
+    assert_eq!(exprs, vec!["(import a.b.c)", "(import util from \"../lib/util.ebi\")", "(import from)"]);
+
+    let cases = [
+        ("import\nx", "expected a module name in `import`, found newline"),
+        ("import a.\n", "expected a module name in `import`, found newline"),
+        ("import a from b", "expected a file path string after `from`, found identifier"),
+        ("import a.b from \"c\"", "`import ... from` binds a single name, found a dotted module path"),
+        ("import a b", "expected a newline after import, found identifier"),
+        ("pub import a", "imports cannot be `pub`"),
+        ("class A {\n    import a\n}", "imports are only allowed at the top level of a file"),
+    ];
+    for (source, message) in cases {
+        let (compiler, _) = parse_exprs(source);
+        let messages = compiler.errors().into_iter().map(|e| e.message).collect::<Vec<_>>();
+        assert_eq!(messages, vec![message.to_string()], "{}", source);
+    }
+}
+
+#[test]
+fn parser_closures() {
+    let cases = [
+        ("|a, b| a + b", "(closure ($a $b) (+ a b))"),
+        ("|| 1", "(closure () 1)"),
+        ("|x: Int| -> Int { x * 2 }", "(closure (x: Int) -> Int { (* x 2)})"),
+        ("map(list, |x| x.y)", "(call map list (closure ($x) (. x y)))"),
+        ("f = |a| |b| a || b", "(= f (closure ($a) (closure ($b) (|| a b))))"),
+        ("g = fn(a) { return a }", "(= g (fn ($a) { (return a)}))"),
+        ("apply(fn(a: Int, b) -> Int {\n    a + b\n}, 1)", "(call apply (fn (a: Int $b) -> Int { (+ a b)}) 1)"),
+    ];
+    for (source, expected) in cases {
+        let (compiler, exprs) = parse_exprs(source);
+        assert!(compiler.errors().is_empty(), "{}: {:?}", source, compiler.errors().iter().map(|e| &e.message).collect::<Vec<_>>());
+        assert_eq!(exprs, vec![expected.to_string()], "{}", source);
+    }
+
+    let (compiler, _) = parse_exprs("|a -> a\n|a| -> Int a");
+    let messages = compiler.errors().into_iter().map(|e| e.message).collect::<Vec<_>>();
+    assert_eq!(messages, vec![
+        "expected `|` to close the closure parameters, found `->`",
+        "expected `{` to begin the closure body, a closure with a return type needs a block body",
+    ]);
+}
+
+#[test]
+fn parser_recovery() {
+    let source = "x y z\nfn f() {}\n) ] }\nclass A {\n    x: Int + 1\n    fn g() {}\n}\na b { c\n    d }\nfn h() {}\n";
+    let (compiler, root) = parse_source(source);
+    let messages = compiler.errors().into_iter().map(|e| e.message).collect::<Vec<_>>();
+    assert_eq!(messages, vec![
+        "expected one of `;`, newline but found identifier",
+        "expected one of `pub`, `fn`, `class`, `struct`, `import`, an expression but found `)`",
+        "expected one of `::`, `<`, `?`, `=`, `;`, newline, `}` but found `+`",
+        "expected one of `;`, newline but found identifier",
+    ]);
+
+    let Ast::Root(_, decls) = root.root() else { panic!() };
+    let exprs = decls.iter().map(|d| sexpr(&compiler, d)).collect::<Vec<_>>();
+    assert_eq!(exprs, vec![
+        "x",
+        "<error>",
+        "(fn f () {})",
+        "<error>",
+        "(class A (field x: Int) <error> (fn g () {}))",
+        "a",
+        "<error>",
+        "(fn h () {})",
+    ]);
+
+    // Error nodes cover exactly the skipped source, braces included.
+    let skipped = decls.iter().filter_map(|d| match d {
+        Ast::Error(token) => Some(text(&compiler, token)),
+        _ => None,
+    });
+    assert_eq!(skipped.collect::<Vec<_>>(), vec!["y z", ") ] }", "b { c\n    d }"]);
+}
+
+#[test]
+fn parser_visitor() {
+    struct Names<'c> {
+        compiler: &'c Compiler,
+        exprs: Vec<String>,
+        types: Vec<String>,
+    }
+
+    impl<'c, 'a> Visitor<'a> for Names<'c> {
+        fn visit_expr(&mut self, ast: &'a Ast<'a>) {
+            if let Ast::Name(name) = ast {
+                self.exprs.push(text(self.compiler, name));
+            }
+            walk_expr(self, ast);
+        }
+        fn visit_type(&mut self, ast: &'a Ast<'a>) {
+            if let Ast::Name(name) = ast {
+                self.types.push(text(self.compiler, name));
+            }
+            walk_type(self, ast);
+        }
+    }
+
+    let source = "class A<T: Eq>: B {\n    x: [T] = f(y)\n    fn m(a: (C)) -> D? {\n        let z: (E, fn(F) -> G) = match a { P { q: 1 } if h => |k: H| k }\n    }\n}\nI::<J>\n";
+    let (compiler, root) = parse_source(source);
+    assert!(compiler.errors().is_empty());
+
+    let mut names = Names { compiler: &compiler, exprs: Vec::new(), types: Vec::new() };
+    names.visit_root(root.root());
+    assert_eq!(names.exprs, vec!["f", "y", "a", "P", "h", "k", "I"]);
+    assert_eq!(names.types, vec!["Eq", "B", "T", "C", "D", "E", "F", "G", "H", "J"]);
+}
+
+#[test]
+fn parser_folder() {
+    struct Identity<'b> {
+        bump: &'b Bump,
+    }
+
+    impl<'a, 'b> Folder<'a, 'b> for Identity<'b> {
+        fn bump(&self) -> &'b Bump {
+            self.bump
+        }
+    }
+
+    // Rewrites `a += b` into `a = a + b`.
+    struct Desugar<'b> {
+        bump: &'b Bump,
+    }
+
+    impl<'a, 'b> Folder<'a, 'b> for Desugar<'b> {
+        fn bump(&self) -> &'b Bump {
+            self.bump
+        }
+        fn fold_expr(&mut self, ast: &'a Ast<'a>) -> Ast<'b> {
+            let Ast::Binop(op, lhs, rhs) = ast else { return walk_fold_expr(self, ast) };
+            if op.kind != TokenKind::AddAssign {
+                return walk_fold_expr(self, ast);
+            }
+            let lhs = self.fold_expr(lhs);
+            let rhs = self.fold_expr(rhs);
+            let lhs: &'b Ast<'b> = self.bump.alloc(lhs);
+            let sum = Ast::Binop(Token { kind: TokenKind::Add, ..op.clone() }, lhs, self.bump.alloc(rhs));
+            Ast::Binop(Token { kind: TokenKind::Assign, ..op.clone() }, lhs, self.bump.alloc(sum))
+        }
+    }
+
+    let source = "/// Doc\npub class A<T>: B {\n    x: Int? = 1\n    fn f(self: A) -> [T] {\n        var i = 0\n        while i < 3 { i += g(|x| x * 2)[0]; continue }\n        for y in ys { return match y { (1..=2, _) | Q::R => 0, S { t, .. } => 1 } }\n        if a { b } else if c {} else { break }\n    }\n}\nimport m.n\nfn h() {}\n";
+    let (compiler, root) = parse_source(source);
+    assert!(compiler.errors().is_empty());
+    let original = sexpr(&compiler, root.root());
+
+    let copy = AstRoot::new(|bump| Identity { bump }.fold_root(root.root()));
+    assert_eq!(sexpr(&compiler, copy.root()), original);
+
+    let desugared = AstRoot::new(|bump| Desugar { bump }.fold_root(root.root()));
+    struct Assigns<'a>(Vec<&'a Ast<'a>>);
+    impl<'a> Visitor<'a> for Assigns<'a> {
+        fn visit_expr(&mut self, ast: &'a Ast<'a>) {
+            if let Ast::Binop(op, _, _) = ast && op.kind == TokenKind::Assign {
+                self.0.push(ast);
+            }
+            walk_expr(self, ast);
+        }
+    }
+    let mut found = Assigns(Vec::new());
+    found.visit_root(desugared.root());
+    let assigns = found.0;
+    assert_eq!(assigns.len(), 1);
+    let Ast::Binop(_, lhs, Ast::Binop(add, sum_lhs, _)) = assigns[0] else { panic!() };
+    assert_eq!(add.kind, TokenKind::Add);
+    assert_eq!(sexpr(&compiler, lhs), "i");
+    assert_eq!(sexpr(&compiler, sum_lhs), "i");
+}
+
+#[test]
+fn parser_node_ids() {
+    let (compiler, first) = parse_source("fn f() {}\n");
+    let file = compiler.add_file("other.ebi", b"fn g() {\n    h(1)\n}\n".to_vec());
+    let second = parse(&compiler, tokenize(&compiler, file.file(), file.data()));
+
+    let first_nodes = compiler.add_nodes(&first);
+    let nodes = compiler.add_nodes(&second);
+    assert!(!nodes.contains(first_nodes.root()) && !first_nodes.contains(nodes.root()));
+    assert_eq!(nodes.id(second.root()), Some(nodes.root()));
+
+    let Ast::Root(_, decls) = second.root() else { panic!() };
+    let Ast::FnDecl(.., body) = &decls[0] else { panic!() };
+    let Ast::Block(_, stmts, _) = body else { panic!() };
+    let call = nodes.id(&stmts[0]).unwrap();
+    let Ast::Call(callee, ..) = &stmts[0] else { panic!() };
+    let name = nodes.id(callee).unwrap();
+    assert_eq!(nodes.parent(name), Some(call));
+    assert_eq!(nodes.ancestors(name).last(), Some(nodes.root()));
+    assert_eq!(nodes.parent(nodes.root()), None);
+    assert_eq!(first_nodes.id(&stmts[0]), None);
+    assert_eq!(compiler.node_table(first_nodes.root()).map(|table| table.len()), Some(first_nodes.len()));
+
+    // Diagnostics on a node id resolve to the node's span when reported.
+    push_error(&compiler, &InternalLocation { file: file!(), line: line!() }, &call, "bad call".to_string());
+    push_error(&compiler, &InternalLocation { file: file!(), line: line!() }, &NodeId::new(1000), "nowhere".to_string());
+
+    // Dropping a map unregisters its ids, reported diagnostics keep their spans.
+    drop(nodes);
+    assert!(compiler.node_table(call).is_none());
+    assert!(compiler.node_table(first_nodes.root()).is_some());
+    let errors = compiler.errors();
+    let span = compiler.span_info(errors[0].location).unwrap();
+    assert_eq!((span.filename(), span.line(), span.column(), span.text().as_ref()), ("other.ebi", 2, 5, "h(1)"));
+    assert!(errors[1].location.is_unknown());
+}
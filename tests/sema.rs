@@ -0,0 +1,227 @@
+use ebi::Compiler;
+use ebi::front::{parse, tokenize};
+use ebi::sema::check;
+
+fn check_source_all(source: &str) -> (Vec<String>, Vec<String>) {
+    let compiler = Compiler::new();
+    let file = compiler.add_file("internal.ebi", source.bytes().collect());
+    let root = parse(&compiler, tokenize(&compiler, file.file(), file.data()));
+    assert!(compiler.errors().is_empty());
+    check(&compiler, file.data(), &compiler.add_nodes(&root));
+    let errors = compiler.errors().into_iter().map(|e| e.message).collect();
+    let warnings = compiler.warnings().into_iter().map(|e| e.message).collect();
+    (errors, warnings)
+}
+
+fn check_source(source: &str) -> Vec<String> {
+    check_source_all(source).0
+}
+
+#[test]
+fn sema_generic_arity() {
+    let source = "
+class Box<T> {
+    value: T
+    next: Box<T>?
+}
+class Pair<A, B: Eq + Box<A>> {}
+fn id<T>(x: T) -> T { return x }
+fn main() {
+    let b: Box<Int> = id::<Box<Int>>(make())
+    let p: Pair<Int, [Box<Float>]>
+}
+";
+    assert!(check_source(source).is_empty());
+
+    let source = "
+class Box<T> {}
+struct Point {}
+fn id<T>(x: T) -> T { return x }
+fn f<T, T>(a: Box, b: Box<Int, Int>, c: Point<Int>, d: T<Int>) -> fn() -> Box {
+    id::<Int, Int>(1)
+}
+";
+    assert_eq!(check_source(source), vec![
+        "duplicate type parameter `T`",
+        "missing type arguments for class `Box`, expected 1 type argument",
+        "class `Box` expects 1 type argument, found 2",
+        "struct `Point` is not generic and does not take type arguments",
+        "type parameter `T` does not take type arguments",
+        "missing type arguments for class `Box`, expected 1 type argument",
+        "function `id` expects 1 type argument, found 2",
+    ]);
+
+    let source = "
+class Box<T> {}
+fn f(x: (Box), y: ([(Box<Int>)])) {}
+";
+    assert_eq!(check_source(source), vec![
+        "missing type arguments for class `Box`, expected 1 type argument",
+    ]);
+}
+
+#[test]
+fn sema_generic_scopes() {
+    let source = "
+class Outer<T> {
+    fn get<U>(a: T, b: U) -> T { a }
+    inner: List<T>
+}
+fn free(a: T) {}
+class T<X> {}
+";
+    assert_eq!(check_source(source), vec![
+        "missing type arguments for class `T`, expected 1 type argument",
+    ]);
+}
+
+fn check_match(arms: &str) -> (Vec<String>, Vec<String>) {
+    let source = format!("struct Point {{\n    x: Int\n    y: Bool\n}}\nfn f() {{\n    match v {{\n{}\n    }}\n}}\n", arms);
+    check_source_all(&source)
+}
+
+#[test]
+fn sema_match_exhaustive() {
+    let exhaustive = [
+        "true => 1\nfalse => 2",
+        "(true, _) => 1\n(false, true) => 2\n(_, false) => 3",
+        "Point { y: true, .. } => 1\nPoint { x, y: false } => 2",
+        "1 => 1\n_ => 2",
+        "'a'..='z' | 'A'..='Z' => 1\nc => 2",
+        "(0..10, true) => 1\n(_, _) => 2",
+        "x if x > 0 => 1\n_ => 2",
+    ];
+    for arms in exhaustive {
+        assert_eq!(check_match(arms), (vec![], vec![]), "{}", arms);
+    }
+
+    let cases = [
+        ("true => 1", "pattern `false` is not covered"),
+        ("(true, _) => 1\n(_, true) => 2", "pattern `(false, false)` is not covered"),
+        ("Point { y: true, .. } => 1", "pattern `Point { y: false, .. }` is not covered"),
+        ("0 => 1\n1..=5 => 2", "pattern `_` is not covered"),
+        ("x if x => 1\nfalse => 2", "pattern `true` is not covered"),
+        ("((true,), 1) => 1", "pattern `((true,), _)` is not covered"),
+    ];
+    for (arms, missing) in cases {
+        let (errors, warnings) = check_match(arms);
+        assert_eq!(errors, vec![format!("non-exhaustive match, {}", missing)], "{}", arms);
+        assert!(warnings.is_empty(), "{}", arms);
+    }
+}
+
+#[test]
+fn sema_match_unreachable() {
+    let cases = [
+        ("_ => 1\ntrue => 2", 1),
+        ("1..=10 => 1\n5 => 2\n_ => 3", 1),
+        ("0..5 => 1\n5..10 => 2\n1..=9 => 3\n_ => 4", 1),
+        ("0..5 => 1\n5..10 => 2\n1..=10 => 3\n_ => 4", 0),
+        ("\"a\" | \"b\" => 1\n\"b\" => 2\n_ => 3", 1),
+        ("true | false => 1\n_ => 2", 1),
+        ("(a, true) => 1\n(b, false) => 2\n(c, d) => 3", 1),
+    ];
+    for (arms, count) in cases {
+        let (errors, warnings) = check_match(arms);
+        assert!(errors.is_empty(), "{}: {:?}", arms, errors);
+        assert_eq!(warnings.len(), count, "{}", arms);
+        assert!(warnings.iter().all(|w| w.starts_with("unreachable match arm")));
+    }
+
+    let (errors, _) = check_match("Point { z, .. } => 1\nPoint { x } => 2\nUnknown { } => 3\n5..1 => 4\nA..=B => 5\n_ => 6");
+    assert_eq!(errors, vec![
+        "`Point` has no field `z`",
+        "pattern does not mention `y`, use `..` to ignore the remaining fields",
+        "cannot find class `Unknown` for this pattern",
+        "range pattern is empty, the lower bound exceeds the upper bound",
+        "range patterns must be bounded by integer or character literals",
+    ]);
+}
+
+#[test]
+fn sema_reports_on_nodes() {
+    let compiler = Compiler::new();
+    let source = "class Box<T> {}\nfn f(x: Box) {\n    match x {\n        5..1 => 1\n        _ => 2\n        (_) => 3\n    }\n}\n";
+    let file = compiler.add_file("internal.ebi", source.bytes().collect());
+    let root = parse(&compiler, tokenize(&compiler, file.file(), file.data()));
+    let nodes = compiler.add_nodes(&root);
+    check(&compiler, file.data(), &nodes);
+    drop(nodes);
+
+    let span_text = |location| compiler.span_info(location).unwrap().text().into_owned();
+    let errors = compiler.errors().into_iter().map(|e| span_text(e.location)).collect::<Vec<_>>();
+    let warnings = compiler.warnings().into_iter().map(|e| span_text(e.location)).collect::<Vec<_>>();
+    assert_eq!(errors, vec!["Box", "5..1"]);
+    assert_eq!(warnings, vec!["5..1", "(_)"]);
+}
+
+fn captures(source: &str) -> (Vec<Vec<String>>, Vec<String>) {
+    let compiler = Compiler::new();
+    let file = compiler.add_file("internal.ebi", source.bytes().collect());
+    let root = parse(&compiler, tokenize(&compiler, file.file(), file.data()));
+    assert!(compiler.errors().is_empty());
+    let closures = check(&compiler, file.data(), &compiler.add_nodes(&root));
+    let names = closures.iter().map(|c| c.captures.iter().map(|c| c.name.clone()).collect()).collect();
+    let errors = compiler.errors().into_iter().map(|e| e.message).collect();
+    (names, errors)
+}
+
+#[test]
+fn sema_closure_captures() {
+    let source = "
+struct Point {
+    x: Int
+    y: Int
+}
+fn f(a: Int, b: Int) {
+    let c = 1
+    let g = |x| x + a + c + global
+    let h = |y| {
+        let d = y
+        |z| z + d + b
+    }
+    for i in items {
+        each(fn(e) { e + i })
+    }
+    match v {
+        Point { x, .. } => || x
+    }
+    let c = 2
+    let k = |c| c
+}
+fn other() {
+    || a
+}
+";
+    let (names, errors) = captures(source);
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(names, vec![
+        vec!["a", "c"],
+        vec!["b"],
+        vec!["d", "b"],
+        vec!["i"],
+        vec!["x"],
+        vec![],
+        vec![],
+    ]);
+}
+
+#[test]
+fn sema_closure_mutable_captures() {
+    let source = "
+fn f() {
+    var count = 0
+    let total = 0
+    let g = || count + total
+    let h = || || count
+    var late = 1
+    let k = |late| late
+}
+";
+    let (names, errors) = captures(source);
+    assert_eq!(names, vec![vec!["count", "total"], vec!["count"], vec!["count"], vec![]]);
+    assert_eq!(errors, vec![
+        "closure captures `count`, which is declared with `var`, closures may only capture `let` bindings",
+        "closure captures `count`, which is declared with `var`, closures may only capture `let` bindings",
+    ]);
+}
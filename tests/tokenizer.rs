@@ -1,6 +1,8 @@
-use ebi::{Compiler, SourceSpan};
-use ebi::ast::{TokenKind};
-use ebi::front::tokenize;
+use ebi::{Compiler, NullErrors, SourceSpan};
+use ebi::ast::{ContextualKeyword, Token, TokenKind, TokenValue, TriviaKind};
+use ebi::front::{
+    ident_name, relex, split_trivia, string_value, token_spelling, tokenize, tokenize_lossless, TextEdit,
+};
 
 #[test]
 fn tokenizer_hello() {
@@ -37,3 +39,508 @@ fn tokenizer_error() {
     assert_eq!(tokens[1].span, SourceSpan::new(f, 6, 9));
     assert_eq!(tokens[2].span, SourceSpan::new(f, 10, 15));
 }
+
+fn lex(source: &str) -> (Compiler, Vec<Token>) {
+    let compiler = Compiler::new();
+    let file = compiler.add_file("internal.ebi", source.bytes().collect());
+    let tokens = tokenize(&compiler, file.file(), file.data()).collect::<Vec<_>>();
+    (compiler, tokens)
+}
+
+#[test]
+fn tokenizer_numbers() {
+    let (compiler, tokens) = lex("0 42 1_000 0xff 0o17 0b1010 1.5 2e3 1.25e-2 3E+1");
+    assert!(compiler.errors().is_empty());
+
+    let values = tokens.iter().map(|t| (t.kind, t.value)).collect::<Vec<_>>();
+    assert_eq!(values, vec![
+        (TokenKind::Integer, TokenValue::Integer(0)),
+        (TokenKind::Integer, TokenValue::Integer(42)),
+        (TokenKind::Integer, TokenValue::Integer(1000)),
+        (TokenKind::Integer, TokenValue::Integer(255)),
+        (TokenKind::Integer, TokenValue::Integer(15)),
+        (TokenKind::Integer, TokenValue::Integer(10)),
+        (TokenKind::Float, TokenValue::Float(1.5)),
+        (TokenKind::Float, TokenValue::Float(2000.0)),
+        (TokenKind::Float, TokenValue::Float(0.0125)),
+        (TokenKind::Float, TokenValue::Float(30.0)),
+    ]);
+}
+
+#[test]
+fn tokenizer_number_errors() {
+    for source in ["0x", "1e", "1.2.3", "0b102", "12abc", "18446744073709551616"] {
+        let (compiler, tokens) = lex(source);
+        assert_eq!(compiler.errors().len(), 1, "{source}");
+        assert_eq!(tokens.len(), 1, "{source}");
+        assert_eq!(tokens[0].value, TokenValue::None, "{source}");
+    }
+
+    let (compiler, tokens) = lex("18446744073709551615");
+    assert!(compiler.errors().is_empty());
+    assert_eq!(tokens[0].value, TokenValue::Integer(u64::MAX));
+}
+
+#[test]
+fn tokenizer_strings() {
+    let source = "\"hi\\n\" r\"a\\b\" r#\"say \"hi\"\"# \"two\nlines\" \"\\u{1F600}\\t\\\\\\\"\"";
+    let (compiler, tokens) = lex(source);
+    assert!(compiler.errors().is_empty());
+    assert!(tokens.iter().all(|t| t.kind == TokenKind::String));
+
+    let values = tokens.iter()
+        .map(|t| string_value(&source.as_bytes()[t.span.begin()..t.span.end()]))
+        .collect::<Vec<_>>();
+    assert_eq!(values, vec!["hi\n", "a\\b", "say \"hi\"", "two\nlines", "\u{1F600}\t\\\""]);
+}
+
+#[test]
+fn tokenizer_chars() {
+    let (compiler, tokens) = lex("'a' '\\n' '\\'' '\u{e4}' '\\u{41}'");
+    assert!(compiler.errors().is_empty());
+
+    let values = tokens.iter().map(|t| t.value).collect::<Vec<_>>();
+    assert_eq!(values, vec![
+        TokenValue::Char('a'),
+        TokenValue::Char('\n'),
+        TokenValue::Char('\''),
+        TokenValue::Char('\u{e4}'),
+        TokenValue::Char('A'),
+    ]);
+
+    for source in ["''", "'ab'", "'a", "'\\q'"] {
+        let (compiler, tokens) = lex(source);
+        assert_eq!(compiler.errors().len(), 1, "{source}");
+        assert_eq!(tokens[0].value, TokenValue::None, "{source}");
+    }
+}
+
+#[test]
+fn tokenizer_string_errors() {
+    let (compiler, tokens) = lex("\"ab\\qc\\u{110000}\" x");
+    let errors = compiler.errors();
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(errors.len(), 2);
+
+    let f = tokens[0].span.file();
+    assert_eq!(errors[0].location, SourceSpan::new(f, 3, 5));
+    assert_eq!(errors[1].location, SourceSpan::new(f, 6, 16));
+
+    let (compiler, tokens) = lex("x \"abc\ndef");
+    let errors = compiler.errors();
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].location, SourceSpan::new(tokens[0].span.file(), 2, 3));
+    assert_eq!(tokens[1].span.end(), 10);
+
+    let (compiler, _) = lex("r##\"abc\"# x");
+    assert_eq!(compiler.errors().len(), 1);
+}
+
+fn lex_bytes(source: &[u8]) -> (Compiler, Vec<Token>) {
+    let compiler = Compiler::new();
+    let file = compiler.add_file("internal.ebi", source.to_vec());
+    let tokens = tokenize(&compiler, file.file(), file.data()).collect::<Vec<_>>();
+    (compiler, tokens)
+}
+
+#[test]
+fn tokenizer_truncated_literals() {
+    // A truncated UTF-8 lead byte at the end of the file must not run past it.
+    let (_, tokens) = lex_bytes(b"\"\\\xf0");
+    assert_eq!((tokens.len(), tokens[0].kind, tokens[0].span.end()), (1, TokenKind::String, 3));
+
+    let (_, tokens) = lex_bytes(b"'\xf0");
+    assert_eq!((tokens.len(), tokens[0].kind, tokens[0].span.end()), (1, TokenKind::Char, 2));
+}
+
+#[test]
+fn tokenizer_comments() {
+    let source = "a // line\n/* block /* nested */ still */ b\n/// doc\n//! inner\n//// plain\nc";
+    let (compiler, tokens) = lex(source);
+    assert!(compiler.errors().is_empty());
+
+    let kinds = tokens.iter().map(|t| t.kind).collect::<Vec<_>>();
+    assert_eq!(kinds, vec![
+        TokenKind::Ident,
+        TokenKind::Newline,
+        TokenKind::Ident,
+        TokenKind::Newline,
+        TokenKind::DocComment,
+        TokenKind::Newline,
+        TokenKind::InnerDocComment,
+        TokenKind::Newline,
+        TokenKind::Newline,
+        TokenKind::Ident,
+    ]);
+
+    let f = tokens[0].span.file();
+    assert_eq!(tokens[4].span, SourceSpan::new(f, 43, 50));
+
+    let (compiler, tokens) = lex("a /* /* */ b");
+    let errors = compiler.errors();
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].location, SourceSpan::new(f, 2, 4));
+}
+
+#[test]
+fn tokenizer_punctuation() {
+    let kinds = [
+        TokenKind::Add, TokenKind::Sub, TokenKind::Mul, TokenKind::Div, TokenKind::Mod,
+        TokenKind::Not, TokenKind::NotEquals, TokenKind::LogicalAnd, TokenKind::LogicalOr,
+        TokenKind::BitAnd, TokenKind::BitOr, TokenKind::BitXor, TokenKind::ShiftLeft, TokenKind::ShiftRight,
+        TokenKind::ParenOpen, TokenKind::ParenClose, TokenKind::BracketOpen, TokenKind::BracketClose,
+        TokenKind::BraceOpen, TokenKind::BraceClose, TokenKind::Comma, TokenKind::Dot, TokenKind::Colon,
+        TokenKind::Semicolon, TokenKind::Arrow, TokenKind::FatArrow, TokenKind::ColonColon,
+        TokenKind::Assign, TokenKind::Equals, TokenKind::Less, TokenKind::LessEquals,
+        TokenKind::Greater, TokenKind::GreaterEquals, TokenKind::AddAssign, TokenKind::SubAssign,
+        TokenKind::MulAssign, TokenKind::DivAssign, TokenKind::ModAssign, TokenKind::BitAndAssign,
+        TokenKind::BitOrAssign, TokenKind::BitXorAssign, TokenKind::ShiftLeftAssign, TokenKind::ShiftRightAssign,
+    ];
+
+    let spellings = kinds.iter()
+        .map(|k| String::from_utf8(token_spelling(*k).unwrap().to_vec()).unwrap())
+        .collect::<Vec<_>>();
+    let (compiler, tokens) = lex(&spellings.join(" "));
+    assert!(compiler.errors().is_empty());
+    assert_eq!(tokens.iter().map(|t| t.kind).collect::<Vec<_>>(), kinds);
+
+    let (compiler, tokens) = lex("a<<=b>>c->d");
+    assert!(compiler.errors().is_empty());
+    assert_eq!(tokens.iter().map(|t| t.kind).collect::<Vec<_>>(), vec![
+        TokenKind::Ident, TokenKind::ShiftLeftAssign, TokenKind::Ident, TokenKind::ShiftRight,
+        TokenKind::Ident, TokenKind::Arrow, TokenKind::Ident,
+    ]);
+
+    assert_eq!(TokenKind::ParenClose.to_string(), "`)`");
+    assert_eq!(TokenKind::BraceOpen.to_string(), "`{`");
+    assert_eq!(TokenKind::Ident.to_string(), "identifier");
+}
+
+fn reconstruct(source: &[u8], tokens: &[Token]) -> Vec<u8> {
+    let mut result = Vec::new();
+    for token in tokens {
+        for span in [token.leading_trivia(), token.span, token.trailing_trivia()] {
+            result.extend_from_slice(&source[span.begin()..span.end()]);
+        }
+    }
+    result
+}
+
+fn lex_lossless(source: &[u8]) -> Vec<Token> {
+    let compiler = Compiler::new();
+    let file = compiler.add_file("internal.ebi", source.to_vec());
+    tokenize_lossless(&compiler, file.file(), file.data()).collect()
+}
+
+#[test]
+fn tokenizer_lossless() {
+    let sources = [
+        "",
+        "   ",
+        "// only a comment",
+        "class A {\n    x + y // trailing\n}\n",
+        "  /* multi\n line */ a\n\n\t/// doc\n  b /* unterminated",
+        "a @@@ b \"unterminated\n string",
+        "\n\n  x\r\n  ",
+    ];
+    for source in sources {
+        let tokens = lex_lossless(source.as_bytes());
+        assert_eq!(tokens.last().map(|t| t.kind), Some(TokenKind::End));
+        assert_eq!(reconstruct(source.as_bytes(), &tokens), source.as_bytes());
+    }
+
+    let source = b"a  // c\n  b";
+    let tokens = lex_lossless(source);
+    let f = tokens[0].span.file();
+    assert_eq!(tokens[0].trailing_trivia(), SourceSpan::new(f, 1, 7));
+    assert_eq!(tokens[2].leading_trivia(), SourceSpan::new(f, 8, 10));
+
+    let pieces = split_trivia(source, tokens[0].trailing_trivia());
+    let kinds = pieces.iter().map(|p| p.kind).collect::<Vec<_>>();
+    assert_eq!(kinds, vec![TriviaKind::Whitespace, TriviaKind::LineComment]);
+}
+
+#[test]
+fn tokenizer_lossless_random() {
+    let fragments: &[&[u8]] = &[
+        b" ", b"\t", b"\n", b"\r\n", b"x", b"12", b"1.5e3", b"0x", b"+", b"<<=", b"{", b"}",
+        b"//", b"///", b"//!", b"/*", b"*/", b"\"", b"\\", b"'", b"r#\"", b"\"#", b"@", b"\xc3\xa4", b"\xff",
+    ];
+
+    let mut seed = 0x2545f4914f6cdd1du64;
+    for _ in 0..500 {
+        let mut source = Vec::new();
+        for _ in 0..(seed % 40) {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            source.extend_from_slice(fragments[(seed % fragments.len() as u64) as usize]);
+        }
+
+        let tokens = lex_lossless(&source);
+        assert_eq!(reconstruct(&source, &tokens), source);
+    }
+}
+
+#[test]
+fn tokenizer_unicode_idents() {
+    let (compiler, tokens) = lex("größe π_2 変数 café");
+    assert!(compiler.errors().is_empty());
+    assert!(compiler.warnings().is_empty());
+    assert_eq!(tokens.len(), 4);
+    assert!(tokens.iter().all(|t| t.kind == TokenKind::Ident));
+
+    let composed = "caf\u{e9}";
+    let decomposed = "cafe\u{301}";
+    assert_eq!(ident_name(composed.as_bytes()), ident_name(decomposed.as_bytes()));
+
+    let (compiler, tokens) = lex("a € b");
+    assert_eq!(tokens.len(), 3);
+    assert_eq!(tokens[1].kind, TokenKind::Error);
+    assert_eq!(compiler.errors().len(), 1);
+}
+
+#[test]
+fn tokenizer_confusable_idents() {
+    let (compiler, _) = lex("pass = 1\n\u{440}\u{430}ss = 2\npass");
+    let warnings = compiler.warnings();
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings[0].message.contains("multiple scripts"));
+    assert!(warnings[1].message.contains("confusable with `pass`"));
+    assert_eq!(warnings[1].location.begin(), 9);
+
+    let (compiler, _) = lex("\u{43f}\u{440}\u{438}\u{432}\u{435}\u{442} \u{43f}\u{440}\u{438}\u{432}\u{435}\u{442} hello");
+    assert!(compiler.warnings().is_empty());
+}
+
+#[test]
+fn tokenizer_keywords() {
+    let source = "fn let var if else while for in return break continue import match pub true false null class struct";
+    let (compiler, tokens) = lex(source);
+    assert!(compiler.errors().is_empty());
+    for (token, word) in tokens.iter().zip(source.split(' ')) {
+        assert_ne!(token.kind, TokenKind::Ident, "{word}");
+        assert_eq!(token_spelling(token.kind), Some(word.as_bytes()));
+    }
+
+    let (compiler, tokens) = lex("from as where");
+    assert!(compiler.errors().is_empty());
+    let values = tokens.iter().map(|t| (t.kind, t.value)).collect::<Vec<_>>();
+    assert_eq!(values, vec![
+        (TokenKind::Ident, TokenValue::Contextual(ContextualKeyword::From)),
+        (TokenKind::Ident, TokenValue::Contextual(ContextualKeyword::As)),
+        (TokenKind::Ident, TokenValue::Contextual(ContextualKeyword::Where)),
+    ]);
+}
+
+#[test]
+fn tokenizer_raw_idents() {
+    let source = "r#class r#from r#foo r";
+    let (compiler, tokens) = lex(source);
+    assert!(compiler.errors().is_empty());
+    assert!(tokens.iter().all(|t| t.kind == TokenKind::Ident && t.value == TokenValue::None));
+
+    let names = tokens.iter()
+        .map(|t| ident_name(&source.as_bytes()[t.span.begin()..t.span.end()]).into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["class", "from", "foo", "r"]);
+}
+
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n.max(1) as u64) as usize
+    }
+}
+
+#[test]
+fn tokenizer_relex_random() {
+    let fragments: &[&[u8]] = &[
+        b" ", b"  ", b"\n", b"\r\n", b"abc", b"x", b"r", b"#", b"12", b"1.5", b"e3", b".", b"0x1f", b"+", b"=",
+        b"<<", b"{", b"}", b"//", b"///", b"/*", b"*/", b"\"", b"\\", b"'", b"r#\"", b"\"#", b"@", b"\xc3\xa4",
+        b"######", b"r####", b"#\"",
+    ];
+
+    let mut rng = Rng(0x9e3779b97f4a7c15);
+    for _ in 0..2000 {
+        let mut source = Vec::new();
+        for _ in 0..rng.next(30) {
+            source.extend_from_slice(fragments[rng.next(fragments.len())]);
+        }
+
+        let begin = rng.next(source.len() + 1);
+        let end = begin + rng.next(source.len() - begin + 1);
+        let mut text = Vec::new();
+        for _ in 0..rng.next(3) {
+            text.extend_from_slice(fragments[rng.next(fragments.len())]);
+        }
+        let edit = TextEdit { range: begin..end, text: &text };
+        let edited = edit.apply(&source);
+
+        let compiler = Compiler::new();
+        let file = compiler.add_file("internal.ebi", Vec::new()).file();
+        for lossless in [false, true] {
+            let full = |source| -> Vec<Token> {
+                match lossless {
+                    false => tokenize(&NullErrors, file, source).collect(),
+                    true => tokenize_lossless(&NullErrors, file, source).collect(),
+                }
+            };
+
+            let mut tokens = full(&source);
+            relex(&NullErrors, file, &edited, &mut tokens, &edit);
+            assert_eq!(tokens, full(&edited), "{:?} -> {:?}", String::from_utf8_lossy(&source), edit.range);
+        }
+    }
+}
+
+#[test]
+fn tokenizer_relex_raw_hashes() {
+    let source = b"r######## x".to_vec();
+    let file = Compiler::new().add_file("internal.ebi", Vec::new()).file();
+    let edit = TextEdit { range: 9..9, text: b"\"" };
+    let edited = edit.apply(&source);
+
+    let mut tokens = tokenize(&NullErrors, file, &source).collect::<Vec<_>>();
+    relex(&NullErrors, file, &edited, &mut tokens, &edit);
+    assert_eq!(tokens, tokenize(&NullErrors, file, &edited).collect::<Vec<_>>());
+    assert_eq!((tokens[0].kind, tokens[0].span.end()), (TokenKind::String, 12));
+}
+
+#[test]
+fn tokenizer_relex_window() {
+    let compiler = Compiler::new();
+    let source = b"a b c d e f\ng h i j k l".to_vec();
+    let file = compiler.add_file("internal.ebi", source.clone()).file();
+
+    let mut tokens = tokenize(&compiler, file, &source).collect::<Vec<_>>();
+    let edit = TextEdit { range: 12..13, text: b"gg" };
+    let relexed = relex(&compiler, file, &edit.apply(&source), &mut tokens, &edit);
+    assert_eq!(relexed, 4..8);
+    assert_eq!(tokens[7].span, SourceSpan::new(file, 12, 14));
+    assert_eq!(tokens.len(), 13);
+    assert_eq!(tokens.last().unwrap().span, SourceSpan::new(file, 23, 24));
+}
+
+// Run with `cargo test --features bench`.
+#[cfg(feature = "bench")]
+#[test]
+fn tokenizer_scalar_matches_fast() {
+    use ebi::{front::tokenize_scalar, SourceFile};
+
+    let fragments: &[&[u8]] = &[
+        b" ", b"        ", b"\t\t", b"\n", b"\r", b"abcdefghijklmnop", b"x_1", b"Z", b"\xc3\xa4bc", b"12345678",
+        b"@", b"\x7f", b"\xff", b"[", b"`", b"{", b"/", b"//", b"/*", b"*/", b"\"", b"'",
+    ];
+
+    let mut rng = Rng(0x1234_5678_9abc_def1);
+    for _ in 0..500 {
+        let mut source = Vec::new();
+        for _ in 0..rng.next(40) {
+            source.extend_from_slice(fragments[rng.next(fragments.len())]);
+        }
+
+        let file = SourceFile::new(0);
+        let fast = tokenize(&NullErrors, file, &source).collect::<Vec<_>>();
+        let scalar = tokenize_scalar(&NullErrors, file, &source).collect::<Vec<_>>();
+        assert_eq!(fast, scalar, "{:?}", String::from_utf8_lossy(&source));
+    }
+}
+
+#[test]
+fn tokenizer_line_endings() {
+    let (compiler, tokens) = lex("a\r\nb\rc\n// x\r\nd");
+    assert!(compiler.errors().is_empty());
+
+    let kinds = tokens.iter().map(|t| (t.kind, t.span.end() - t.span.begin())).collect::<Vec<_>>();
+    assert_eq!(kinds, vec![
+        (TokenKind::Ident, 1),
+        (TokenKind::Newline, 2),
+        (TokenKind::Ident, 1),
+        (TokenKind::Newline, 1),
+        (TokenKind::Ident, 1),
+        (TokenKind::Newline, 1),
+        (TokenKind::Newline, 2),
+        (TokenKind::Ident, 1),
+    ]);
+
+    let positions = tokens
+        .iter()
+        .filter(|t| t.kind == TokenKind::Ident)
+        .map(|t| {
+            let info = compiler.span_info(t.span).unwrap();
+            (info.line(), info.column())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(positions, vec![(1, 1), (2, 1), (3, 1), (5, 1)]);
+
+    let (compiler, tokens) = lex("'a\r'");
+    assert_eq!(compiler.errors()[0].message, "unterminated character literal");
+    assert_eq!(tokens[1].kind, TokenKind::Newline);
+}
+
+#[test]
+fn tokenizer_byte_order_mark() {
+    let source = "\u{feff}class A\r\n";
+    let (compiler, tokens) = lex(source);
+    assert!(compiler.errors().is_empty());
+    assert_eq!(tokens[0].kind, TokenKind::KeywordClass);
+    assert_eq!(tokens[0].span.begin(), 3);
+
+    let info = compiler.span_info(tokens[1].span).unwrap();
+    assert_eq!((info.line(), info.column()), (1, 7));
+
+    let lossless = lex_lossless(source.as_bytes());
+    assert_eq!(reconstruct(source.as_bytes(), &lossless), source.as_bytes());
+    let trivia = split_trivia(source.as_bytes(), lossless[0].leading_trivia());
+    assert_eq!(trivia.iter().map(|p| p.kind).collect::<Vec<_>>(), vec![TriviaKind::ByteOrderMark]);
+}
+
+#[test]
+fn tokenizer_invalid_utf8() {
+    let compiler = Compiler::new();
+    let source = b"a \xff\xfe b \"\xc3\" \xe2\x82".to_vec();
+    let file = compiler.add_file("internal.ebi", source);
+    let tokens = tokenize(&compiler, file.file(), file.data()).collect::<Vec<_>>();
+    assert_eq!(tokens[0].kind, TokenKind::Ident);
+
+    let errors = compiler.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "invalid UTF-8 in source file at byte offset 2");
+    assert_eq!(errors[0].location, SourceSpan::new(file.file(), 2, 3));
+
+    let info = compiler.span_info(tokens[2].span).unwrap();
+    assert_eq!((info.line(), info.column()), (1, 6));
+}
+
+#[test]
+fn tokenizer_invalid_utf8_in_literals() {
+    let cases: [(&[u8], TokenKind, usize); 5] = [
+        (b"\"a\xffb\" x", TokenKind::String, 5),
+        (b"\"\xe2\x82\" x", TokenKind::String, 4),
+        (b"'\xff' x", TokenKind::Char, 3),
+        (b"'\xe2\x82' x", TokenKind::Char, 4),
+        (b"\"\\\xf0\x9f\" x", TokenKind::String, 5),
+    ];
+    for (source, kind, end) in cases {
+        let (compiler, tokens) = lex_bytes(source);
+        let name = String::from_utf8_lossy(source);
+        assert_eq!((tokens[0].kind, tokens[0].span.end()), (kind, end), "{:?}", name);
+        assert_eq!(tokens[0].value, TokenValue::None, "{:?}", name);
+        assert_eq!(tokens[1].kind, TokenKind::Ident, "{:?}", name);
+        let messages = compiler.errors().into_iter().map(|e| e.message).collect::<Vec<_>>();
+        assert!(messages.iter().any(|m| m.starts_with("invalid UTF-8")), "{:?}: {:?}", name, messages);
+    }
+
+    // An escape of an invalid byte is reported as unknown and skips just that byte.
+    let (compiler, tokens) = lex_bytes(b"'\\\xff' x");
+    let errors = compiler.errors();
+    assert_eq!(tokens[0].span.end(), 4);
+    assert!(errors.iter().any(|e| e.message == "unknown escape sequence" && e.location == SourceSpan::new(tokens[0].span.file(), 1, 3)));
+}